@@ -0,0 +1,240 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use bpx_api_types::{
+    capital::Balance,
+    markets::Market,
+    order::{ExecuteOrderPayload, Side},
+};
+use rust_decimal::Decimal;
+
+use crate::error::{Error, Result};
+use crate::BpxClient;
+
+/// A balance update fed to `BalanceTracker::apply`, e.g. from a private account stream.
+#[derive(Debug, Clone)]
+pub enum BalanceEvent {
+    Snapshot(HashMap<String, Balance>),
+    Update { symbol: String, balance: Balance },
+}
+
+/// Funds moved from available to locked locally while an order is in flight.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reservation {
+    pub id: u64,
+    pub symbol: String,
+    pub amount: Decimal,
+}
+
+/// A balance whose local copy disagreed with `get_balances` by more than the tolerance.
+#[derive(Debug, Clone)]
+pub struct BalanceDrift {
+    pub symbol: String,
+    pub local: Balance,
+    pub exchange: Balance,
+}
+
+#[derive(Debug, Default)]
+struct Ledger {
+    balances: HashMap<String, Balance>,
+    reservations: HashMap<u64, Reservation>,
+    next_reservation_id: u64,
+}
+
+impl Ledger {
+    fn reserved(&self, symbol: &str) -> Decimal {
+        self.reservations
+            .values()
+            .filter(|r| r.symbol == symbol)
+            .map(|r| r.amount)
+            .sum()
+    }
+
+    /// The exchange balance with local reservations moved from available to locked.
+    fn effective(&self, symbol: &str) -> Option<Balance> {
+        let balance = self.balances.get(symbol)?;
+        let reserved = self.reserved(symbol);
+        Some(Balance {
+            available: balance.available - reserved,
+            locked: balance.locked + reserved,
+            staked: balance.staked,
+        })
+    }
+}
+
+/// Keeps the latest account balances, updated from `apply` and checked against `get_balances`
+/// by `reconcile`.
+///
+/// Reserve funds when an order is sent and release the reservation once the exchange has
+/// acknowledged or rejected it; from then on the exchange's own locked amount covers it.
+#[derive(Debug, Clone)]
+pub struct BalanceTracker {
+    client: BpxClient,
+    tolerance: Decimal,
+    ledger: Arc<Mutex<Ledger>>,
+}
+
+impl BalanceTracker {
+    /// `tolerance` is the largest difference per balance field that `reconcile` accepts
+    /// without reporting drift.
+    pub fn new(client: BpxClient, tolerance: Decimal) -> Self {
+        Self {
+            client,
+            tolerance,
+            ledger: Arc::default(),
+        }
+    }
+
+    fn ledger(&self) -> MutexGuard<'_, Ledger> {
+        self.ledger.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn apply(&self, event: BalanceEvent) {
+        let mut ledger = self.ledger();
+        match event {
+            BalanceEvent::Snapshot(balances) => ledger.balances = balances,
+            BalanceEvent::Update { symbol, balance } => {
+                ledger.balances.insert(symbol, balance);
+            }
+        }
+    }
+
+    /// The balance of `symbol`, including local reservations.
+    pub fn balance(&self, symbol: &str) -> Option<Balance> {
+        self.ledger().effective(symbol)
+    }
+
+    /// Every balance, including local reservations.
+    pub fn balances(&self) -> HashMap<String, Balance> {
+        let ledger = self.ledger();
+        ledger
+            .balances
+            .keys()
+            .filter_map(|symbol| Some((symbol.clone(), ledger.effective(symbol)?)))
+            .collect()
+    }
+
+    /// Moves `amount` of `symbol` from available to locked, failing when not enough is
+    /// available.
+    pub fn reserve(&self, symbol: &str, amount: Decimal) -> Result<Reservation> {
+        let mut ledger = self.ledger();
+        let available = ledger
+            .effective(symbol)
+            .map_or(Decimal::ZERO, |b| b.available);
+        if amount > available {
+            return Err(Error::InvalidRequest(format!(
+                "cannot reserve {amount} {symbol}, only {available} available"
+            )));
+        }
+
+        ledger.next_reservation_id += 1;
+        let reservation = Reservation {
+            id: ledger.next_reservation_id,
+            symbol: symbol.to_string(),
+            amount,
+        };
+        ledger
+            .reservations
+            .insert(reservation.id, reservation.clone());
+        Ok(reservation)
+    }
+
+    /// Reserves what `payload` can spend: the quote asset for bids and the base asset for
+    /// asks. Bids sized in the base asset need a price to be reserved.
+    pub fn reserve_for_order(
+        &self,
+        payload: &ExecuteOrderPayload,
+        market: &Market,
+    ) -> Result<Reservation> {
+        let (symbol, amount) = match &payload.side {
+            Side::Bid => {
+                let amount = match (payload.quote_quantity, payload.price, payload.quantity) {
+                    (Some(quote_quantity), _, _) => quote_quantity,
+                    (None, Some(price), Some(quantity)) => price * quantity,
+                    _ => {
+                        return Err(Error::InvalidRequest(
+                            "bids need a quote quantity or a price to reserve funds".to_string(),
+                        ))
+                    }
+                };
+                (&market.quote_symbol, amount)
+            }
+            Side::Ask => {
+                let amount = payload.quantity.ok_or_else(|| {
+                    Error::InvalidRequest("asks need a quantity to reserve funds".to_string())
+                })?;
+                (&market.base_symbol, amount)
+            }
+            Side::Unknown(side) => {
+                return Err(Error::InvalidRequest(format!("unknown order side {side}")))
+            }
+        };
+        self.reserve(symbol, amount)
+    }
+
+    pub fn release(&self, reservation_id: u64) -> Option<Reservation> {
+        self.ledger().reservations.remove(&reservation_id)
+    }
+
+    /// Replaces the local balances with `get_balances`, returning those that had drifted by
+    /// more than the tolerance.
+    pub async fn reconcile(&self) -> Result<Vec<BalanceDrift>> {
+        let exchange = self.client.get_balances().await?;
+        let mut ledger = self.ledger();
+
+        let zero = Balance {
+            available: Decimal::ZERO,
+            locked: Decimal::ZERO,
+            staked: Decimal::ZERO,
+        };
+        let mut symbols = ledger.balances.keys().cloned().collect::<Vec<_>>();
+        symbols.extend(
+            exchange
+                .keys()
+                .filter(|s| !ledger.balances.contains_key(*s))
+                .cloned(),
+        );
+
+        let drift = symbols
+            .into_iter()
+            .filter_map(|symbol| {
+                let local = ledger.balances.get(&symbol).unwrap_or(&zero);
+                let remote = exchange.get(&symbol).unwrap_or(&zero);
+                let drifted = (local.available - remote.available).abs() > self.tolerance
+                    || (local.locked - remote.locked).abs() > self.tolerance
+                    || (local.staked - remote.staked).abs() > self.tolerance;
+                drifted.then(|| BalanceDrift {
+                    local: local.clone(),
+                    exchange: remote.clone(),
+                    symbol,
+                })
+            })
+            .collect();
+
+        ledger.balances = exchange;
+        Ok(drift)
+    }
+
+    /// Calls `reconcile` every `period`, logging drift and failures. Runs until the future is
+    /// dropped.
+    pub async fn run_reconciliation(&self, period: Duration) {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            match self.reconcile().await {
+                Ok(drift) => {
+                    for d in drift {
+                        tracing::warn!(
+                            symbol = d.symbol,
+                            local = ?d.local,
+                            exchange = ?d.exchange,
+                            "balance drift"
+                        );
+                    }
+                }
+                Err(e) => tracing::warn!("balance reconciliation failed: {e}"),
+            }
+        }
+    }
+}
@@ -1,13 +1,61 @@
-use crate::error::Result;
+use crate::error::{error_for_status, Error, Result};
+use crate::pagination::{paginate, PageOptions, Paged};
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream};
 use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
 
 use bpx_api_types::{
-    capital::{Balance, Deposit, DepositAddress, RequestWithdrawalPayload, Withdrawal},
+    capital::{
+        Balance, Deposit, DepositAddress, RequestWithdrawalPayload, Withdrawal, WithdrawalStatus,
+    },
     Blockchain,
 };
 
+use crate::withdrawal_policy::WithdrawalPolicy;
 use crate::BpxClient;
 
+const WITHDRAWAL_PAGE_SIZE: i64 = 100;
+const WITHDRAWAL_POLL_MIN: Duration = Duration::from_secs(2);
+const WITHDRAWAL_POLL_MAX: Duration = Duration::from_secs(60);
+
+static NEXT_WITHDRAWAL_CLIENT_ID: AtomicU32 = AtomicU32::new(0);
+
+/// A change seen by `BpxClient::track_withdrawal`: a new status or a transaction hash that
+/// just appeared.
+#[derive(Debug, Clone)]
+pub struct WithdrawalUpdate {
+    pub previous_status: Option<WithdrawalStatus>,
+    pub withdrawal: Withdrawal,
+}
+
+impl WithdrawalUpdate {
+    pub fn status(&self) -> &WithdrawalStatus {
+        &self.withdrawal.status
+    }
+
+    pub fn transaction_hash(&self) -> Option<&str> {
+        self.withdrawal.transaction_hash.as_deref()
+    }
+
+    /// Whether the withdrawal can no longer change.
+    pub fn is_final(&self) -> bool {
+        matches!(
+            self.withdrawal.status,
+            WithdrawalStatus::Confirmed | WithdrawalStatus::Void
+        )
+    }
+}
+
+struct TrackState<'a> {
+    client: &'a BpxClient,
+    id: i32,
+    last: Option<Withdrawal>,
+    delay: Duration,
+    done: bool,
+}
+
 impl BpxClient {
     pub async fn get_balances(&self) -> Result<HashMap<String, Balance>> {
         let url = format!("{}/api/v1/capital", self.base_url);
@@ -21,15 +69,21 @@ impl BpxClient {
         offset: Option<i64>,
     ) -> Result<Vec<Deposit>> {
         let mut url = format!("{}/wapi/v1/capital/deposits", self.base_url);
-        for (k, v) in [("limit", limit), ("offset", offset)] {
-            if let Some(v) = v {
-                url.push_str(&format!("&{}={}", k, v));
-            }
-        }
+        push_paging(&mut url, limit, offset);
         let res = self.get(url).await?;
         res.json().await.map_err(Into::into)
     }
 
+    /// Streams every deposit, newest first, paging through `get_deposits`.
+    pub fn deposits_stream(
+        &self,
+        options: PageOptions,
+    ) -> impl Stream<Item = Result<Paged<Deposit>>> + '_ {
+        paginate(options, move |limit, offset| {
+            self.get_deposits(Some(limit), Some(offset))
+        })
+    }
+
     pub async fn get_deposit_address(&self, blockchain: Blockchain) -> Result<DepositAddress> {
         let url = format!(
             "{}/wapi/v1/capital/deposit/address?blockchain={}",
@@ -45,17 +99,203 @@ impl BpxClient {
         offset: Option<i64>,
     ) -> Result<Vec<Withdrawal>> {
         let mut url = format!("{}/wapi/v1/capital/withdrawals", self.base_url);
-        for (k, v) in [("limit", limit), ("offset", offset)] {
-            if let Some(v) = v {
-                url.push_str(&format!("{}={}&", k, v));
-            }
-        }
+        push_paging(&mut url, limit, offset);
         let res = self.get(url).await?;
         res.json().await.map_err(Into::into)
     }
 
-    pub async fn request_withdrawal(&self, payload: RequestWithdrawalPayload) -> Result<()> {
+    /// Streams every withdrawal, newest first, paging through `get_withdrawals`.
+    pub fn withdrawals_stream(
+        &self,
+        options: PageOptions,
+    ) -> impl Stream<Item = Result<Paged<Withdrawal>>> + '_ {
+        paginate(options, move |limit, offset| {
+            self.get_withdrawals(Some(limit), Some(offset))
+        })
+    }
+
+    /// Requests a withdrawal and returns the record it created. Payloads without a
+    /// `client_id` are given one so that the record can be found if the response doesn't
+    /// carry it.
+    pub async fn request_withdrawal(
+        &self,
+        mut payload: RequestWithdrawalPayload,
+    ) -> Result<Withdrawal> {
+        payload.blockchain.validate_address(&payload.address)?;
+        let client_id = payload
+            .client_id
+            .get_or_insert_with(generate_withdrawal_client_id)
+            .clone();
+
         let endpoint = format!("{}/wapi/v1/capital/withdrawals", self.base_url);
-        self.post(endpoint, payload).await.map(|_| ())
+        let now = Utc::now();
+        if let Some(policy) = &self.withdrawal_policy {
+            policy.authorize(&payload, now)?;
+            if policy.is_dry_run() {
+                tracing::info!(
+                    symbol = payload.symbol,
+                    quantity = %payload.quantity,
+                    blockchain = %payload.blockchain,
+                    address = payload.address,
+                    "dry run: withdrawal not sent"
+                );
+                return Err(Error::WithdrawalDryRun);
+            }
+        }
+
+        #[cfg(feature = "totp")]
+        if let (None, Some(totp)) = (&payload.two_factor_token, &self.totp) {
+            match totp.fresh_code().await {
+                Ok(code) => payload.two_factor_token = Some(code),
+                Err(e) => {
+                    if let Some(policy) = &self.withdrawal_policy {
+                        revert_quota(policy, &payload, now);
+                    }
+                    return Err(e);
+                }
+            }
+        }
+
+        // Only a refusal by the exchange frees the quota; after any other failure the
+        // withdrawal may have gone through.
+        let result = self.send_withdrawal(endpoint, &payload, &client_id).await;
+        if let (Err(e), Some(policy)) = (&result, &self.withdrawal_policy) {
+            if e.is_refusal() {
+                revert_quota(policy, &payload, now);
+            }
+        }
+        result
+    }
+
+    /// Posts the withdrawal. A response that isn't the new record is followed by a lookup on
+    /// `client_id`; failing that, the outcome is unknown rather than refused.
+    async fn send_withdrawal(
+        &self,
+        endpoint: String,
+        payload: &RequestWithdrawalPayload,
+        client_id: &str,
+    ) -> Result<Withdrawal> {
+        let res = error_for_status(self.post(endpoint, payload).await?).await?;
+        let body = res.bytes().await?;
+        if let Ok(withdrawal) = serde_json::from_slice::<Withdrawal>(&body) {
+            return Ok(withdrawal);
+        }
+
+        let recent = self
+            .get_withdrawals(Some(WITHDRAWAL_PAGE_SIZE), None)
+            .await?;
+        recent
+            .into_iter()
+            .find(|w| w.client_id.as_deref() == Some(client_id))
+            .ok_or_else(|| {
+                Error::InvalidRequest(format!(
+                    "withdrawal {client_id} was accepted but not found: {}",
+                    String::from_utf8_lossy(&body)
+                ))
+            })
+    }
+
+    /// Finds a withdrawal by id, paging back until it or an older one is found.
+    pub async fn get_withdrawal(&self, id: i32) -> Result<Option<Withdrawal>> {
+        let mut offset = 0;
+        loop {
+            let page = self
+                .get_withdrawals(Some(WITHDRAWAL_PAGE_SIZE), Some(offset))
+                .await?;
+            let exhausted = (page.len() as i64) < WITHDRAWAL_PAGE_SIZE;
+            let passed = page.iter().any(|w| w.id < id);
+            if let Some(withdrawal) = page.into_iter().find(|w| w.id == id) {
+                return Ok(Some(withdrawal));
+            }
+            if exhausted || passed {
+                return Ok(None);
+            }
+            offset += WITHDRAWAL_PAGE_SIZE;
+        }
+    }
+
+    /// Polls a withdrawal until it is confirmed or void, yielding every status change and the
+    /// transaction hash as soon as it appears. The poll interval backs off while nothing
+    /// changes. Failed polls are yielded and retried; the stream ends with an error if the
+    /// withdrawal can't be found.
+    pub fn track_withdrawal(&self, id: i32) -> impl Stream<Item = Result<WithdrawalUpdate>> + '_ {
+        let state = TrackState {
+            client: self,
+            id,
+            last: None,
+            delay: Duration::ZERO,
+            done: false,
+        };
+
+        stream::unfold(state, |mut state| async move {
+            loop {
+                if state.done {
+                    return None;
+                }
+                tokio::time::sleep(state.delay).await;
+                state.delay = (state.delay * 2).clamp(WITHDRAWAL_POLL_MIN, WITHDRAWAL_POLL_MAX);
+
+                let withdrawal = match state.client.get_withdrawal(state.id).await {
+                    Ok(Some(withdrawal)) => withdrawal,
+                    Ok(None) => {
+                        state.done = true;
+                        let e = Error::InvalidRequest(format!("withdrawal {} not found", state.id));
+                        return Some((Err(e), state));
+                    }
+                    Err(e) => return Some((Err(e), state)),
+                };
+
+                let previous = state.last.as_ref();
+                let changed = previous.is_none_or(|last| {
+                    last.status != withdrawal.status
+                        || last.transaction_hash != withdrawal.transaction_hash
+                });
+                if !changed {
+                    continue;
+                }
+
+                let update = WithdrawalUpdate {
+                    previous_status: previous.map(|last| last.status.clone()),
+                    withdrawal: withdrawal.clone(),
+                };
+                state.done = update.is_final();
+                state.delay = WITHDRAWAL_POLL_MIN;
+                state.last = Some(withdrawal);
+                return Some((Ok(update), state));
+            }
+        })
+    }
+}
+
+/// Gives back the quota of a withdrawal that wasn't made. A failure to save the ledger is
+/// logged so that it doesn't hide why the withdrawal failed.
+fn revert_quota(
+    policy: &WithdrawalPolicy,
+    payload: &RequestWithdrawalPayload,
+    authorized_at: DateTime<Utc>,
+) {
+    if let Err(e) = policy.revert(payload, authorized_at) {
+        tracing::warn!(
+            symbol = payload.symbol,
+            quantity = %payload.quantity,
+            "failed to give back withdrawal quota: {e}"
+        );
+    }
+}
+
+/// Unique within the process and, being seeded from the clock, across restarts.
+fn generate_withdrawal_client_id() -> String {
+    let millis = Utc::now().timestamp_millis();
+    let sequence = NEXT_WITHDRAWAL_CLIENT_ID.fetch_add(1, Ordering::Relaxed);
+    format!("{millis}-{sequence}")
+}
+
+fn push_paging(url: &mut String, limit: Option<i64>, offset: Option<i64>) {
+    let params = [("limit", limit), ("offset", offset)]
+        .into_iter()
+        .filter_map(|(k, v)| v.map(|v| (k, v)));
+    for (i, (k, v)) in params.enumerate() {
+        let separator = if i == 0 { '?' } else { '&' };
+        url.push_str(&format!("{separator}{k}={v}"));
     }
 }
@@ -0,0 +1,191 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use bpx_api_types::capital::{Deposit, DepositSource, DepositStatus};
+use futures::{stream, Stream};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::{write_atomically, BpxClient};
+
+const DEPOSIT_PAGE_SIZE: i64 = 100;
+
+/// A deposit reported by `DepositWatcher`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DepositEvent {
+    /// A new deposit that is still waiting for confirmations.
+    DepositSeen {
+        id: i32,
+        source: DepositSource,
+        symbol: String,
+        quantity: Decimal,
+        confirmation_block_number: Option<i32>,
+    },
+    /// A deposit that has been credited, whether or not it was seen pending first.
+    DepositConfirmed {
+        id: i32,
+        source: DepositSource,
+        symbol: String,
+        quantity: Decimal,
+        confirmation_block_number: Option<i32>,
+    },
+}
+
+impl DepositEvent {
+    fn seen(deposit: &Deposit) -> Self {
+        Self::DepositSeen {
+            id: deposit.id,
+            source: deposit.source.clone(),
+            symbol: deposit.symbol.clone(),
+            quantity: deposit.quantity,
+            confirmation_block_number: deposit.confirmation_block_number,
+        }
+    }
+
+    fn confirmed(deposit: &Deposit) -> Self {
+        Self::DepositConfirmed {
+            id: deposit.id,
+            source: deposit.source.clone(),
+            symbol: deposit.symbol.clone(),
+            quantity: deposit.quantity,
+            confirmation_block_number: deposit.confirmation_block_number,
+        }
+    }
+}
+
+/// What a `DepositWatcher` has already reported, persisted between runs.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DepositCursor {
+    /// The newest deposit seen. Deposits up to it are only reported again when they confirm.
+    pub last_id: Option<i32>,
+    /// Deposits reported as seen that are still pending. They leave once they confirm or end
+    /// in any other status.
+    pub pending: BTreeSet<i32>,
+}
+
+impl DepositCursor {
+    /// The oldest deposit a poll has to page back to.
+    fn floor(&self) -> Option<i32> {
+        self.pending.iter().copied().chain(self.last_id).min()
+    }
+}
+
+/// Polls `get_deposits` and reports each deposit once when it appears and once when it is
+/// confirmed. The cursor is saved to a file after every poll, so a restarted watcher picks up
+/// where the last one stopped.
+///
+/// Without a saved cursor the first poll only reports deposits that are still pending;
+/// deposits confirmed before the watcher started are taken as already handled.
+#[derive(Debug, Clone)]
+pub struct DepositWatcher {
+    client: BpxClient,
+    cursor_path: PathBuf,
+    cursor: DepositCursor,
+}
+
+impl DepositWatcher {
+    /// Creates a watcher that keeps its cursor in `cursor_file`, loading it if it already
+    /// exists.
+    pub fn new(client: BpxClient, cursor_file: impl AsRef<Path>) -> Result<Self> {
+        let cursor_path = cursor_file.as_ref().to_path_buf();
+        let cursor = if cursor_path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(&cursor_path)?)?
+        } else {
+            DepositCursor::default()
+        };
+        Ok(Self {
+            client,
+            cursor_path,
+            cursor,
+        })
+    }
+
+    pub fn cursor(&self) -> &DepositCursor {
+        &self.cursor
+    }
+
+    /// Fetches deposits until the cursor is reached and returns the events since the last
+    /// poll, oldest first. The cursor is only advanced once it has been saved.
+    pub async fn poll(&mut self) -> Result<Vec<DepositEvent>> {
+        let deposits = self.fetch().await?;
+        let mut cursor = self.cursor.clone();
+        let first_run = cursor.last_id.is_none();
+
+        let mut events = Vec::new();
+        for deposit in deposits.iter().rev() {
+            let is_new = cursor.last_id.is_none_or(|last_id| deposit.id > last_id);
+            match (&deposit.status, is_new) {
+                (DepositStatus::Pending, true) => {
+                    events.push(DepositEvent::seen(deposit));
+                    cursor.pending.insert(deposit.id);
+                }
+                (DepositStatus::Pending, false) => {}
+                (DepositStatus::Confirmed, true) => {
+                    if !first_run {
+                        events.push(DepositEvent::confirmed(deposit));
+                    }
+                }
+                (DepositStatus::Confirmed, false) => {
+                    if cursor.pending.remove(&deposit.id) {
+                        events.push(DepositEvent::confirmed(deposit));
+                    }
+                }
+                // Any other status is final as far as the watcher is concerned.
+                (DepositStatus::Unknown(_), _) => {
+                    cursor.pending.remove(&deposit.id);
+                }
+            }
+        }
+        cursor.last_id = deposits.iter().map(|d| d.id).chain(cursor.last_id).max();
+
+        write_atomically(&self.cursor_path, &serde_json::to_string_pretty(&cursor)?)?;
+        self.cursor = cursor;
+        Ok(events)
+    }
+
+    /// Deposits newer than the cursor's floor, newest first. Without a cursor only the latest
+    /// page is fetched.
+    async fn fetch(&self) -> Result<Vec<Deposit>> {
+        let floor = self.cursor.floor();
+        let mut deposits = Vec::new();
+        let mut offset = 0;
+        loop {
+            let page = self
+                .client
+                .get_deposits(Some(DEPOSIT_PAGE_SIZE), Some(offset))
+                .await?;
+            let exhausted = (page.len() as i64) < DEPOSIT_PAGE_SIZE;
+            let reached = floor.is_none_or(|floor| page.iter().any(|d| d.id <= floor));
+            deposits.extend(page);
+            if exhausted || reached {
+                return Ok(deposits);
+            }
+            offset += DEPOSIT_PAGE_SIZE;
+        }
+    }
+
+    /// Polls every `period`, starting straight away, and yields each event. Failed polls are
+    /// yielded and retried after the next period.
+    pub fn watch(self, period: Duration) -> impl Stream<Item = Result<DepositEvent>> {
+        let state = (self, Duration::ZERO, VecDeque::<DepositEvent>::new());
+
+        stream::unfold(
+            state,
+            move |(mut watcher, mut delay, mut buffered)| async move {
+                loop {
+                    if let Some(event) = buffered.pop_front() {
+                        return Some((Ok(event), (watcher, delay, buffered)));
+                    }
+                    tokio::time::sleep(delay).await;
+                    delay = period;
+                    match watcher.poll().await {
+                        Ok(events) => buffered.extend(events),
+                        Err(e) => return Some((Err(e), (watcher, delay, buffered))),
+                    }
+                }
+            },
+        )
+    }
+}
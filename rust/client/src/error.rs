@@ -1,3 +1,11 @@
+use bpx_api_types::{address::AddressError, order::Order};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+
+use crate::risk::RiskRule;
+use crate::validation::ValidationError;
+use crate::withdrawal_policy::PolicyViolation;
+
 pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, thiserror::Error)]
@@ -20,12 +28,95 @@ pub enum Error {
     #[error(transparent)]
     Utf8(#[from] std::str::Utf8Error),
 
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+
+    #[error(transparent)]
+    Csv(#[from] csv::Error),
+
     #[error(transparent)]
     Base64Decode(#[from] base64::DecodeError),
 
     #[error("Invalid secret key")]
     SecretKey,
 
+    #[cfg(feature = "totp")]
+    #[error("Invalid TOTP secret")]
+    TotpSecret,
+
+    /// The exchange answered with an error. Only `Error::is_refusal` ones are definite.
+    #[error(transparent)]
+    Api(#[from] ApiError),
+
     #[error("Invalid request: {0}")]
     InvalidRequest(String),
+
+    #[error(transparent)]
+    Validation(#[from] ValidationError),
+
+    #[error("Order rejected by risk rule {rule}: {detail}")]
+    RiskRejected { rule: RiskRule, detail: String },
+
+    #[error(transparent)]
+    InvalidAddress(#[from] AddressError),
+
+    #[error(transparent)]
+    WithdrawalPolicy(#[from] PolicyViolation),
+
+    #[error("Withdrawal not sent: the withdrawal policy is in dry-run mode")]
+    WithdrawalDryRun,
+
+    /// A candle whose high and low don't bound its open and close.
+    #[error("Inconsistent candle for {symbol} starting at {start}")]
+    InconsistentKline {
+        symbol: String,
+        start: DateTime<Utc>,
+    },
+
+    #[error("Timed out waiting for order")]
+    OrderTimeout { last_known: Option<Box<Order>> },
+}
+
+impl Error {
+    /// Whether the exchange definitely refused the request, so nothing it asked for happened.
+    /// Server errors, rate limiting and transport failures leave the outcome unknown.
+    pub fn is_refusal(&self) -> bool {
+        matches!(self, Error::Api(e) if e.is_refusal())
+    }
+}
+
+/// An error returned by the exchange in place of a result, e.g. for one leg of a batch order.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, thiserror::Error)]
+#[error("{code}: {message}")]
+pub struct ApiError {
+    pub code: String,
+    pub message: String,
+    /// The HTTP status of the response, when the error was the whole response rather than one
+    /// entry of it.
+    #[serde(skip)]
+    pub status: Option<u16>,
+}
+
+impl ApiError {
+    /// A 4xx other than a timeout or rate limit, or an error for one entry of a response.
+    pub fn is_refusal(&self) -> bool {
+        self.status
+            .is_none_or(|status| (400..500).contains(&status) && status != 408 && status != 429)
+    }
+}
+
+/// Turns a non-2xx response into `Error::Api`, using the exchange's error body when it has one.
+pub(crate) async fn error_for_status(res: reqwest::Response) -> Result<reqwest::Response> {
+    let status = res.status();
+    if status.is_success() {
+        return Ok(res);
+    }
+    let body = res.text().await?;
+    let mut error = serde_json::from_str(&body).unwrap_or_else(|_| ApiError {
+        code: status.to_string(),
+        message: body,
+        status: None,
+    });
+    error.status = Some(status.as_u16());
+    Err(Error::Api(error))
 }
@@ -0,0 +1,49 @@
+use bpx_api_types::{
+    history::{Fill, HistoryQuery},
+    order::Order,
+};
+use futures::Stream;
+
+use crate::error::Result;
+use crate::pagination::{paginate, PageOptions, Paged};
+use crate::BpxClient;
+
+impl BpxClient {
+    pub async fn get_order_history(&self, query: &HistoryQuery) -> Result<Vec<Order>> {
+        let url = history_url(&self.base_url, "/wapi/v1/history/orders", query);
+        let res = self.get(url).await?;
+        res.json().await.map_err(Into::into)
+    }
+
+    pub async fn get_fill_history(&self, query: &HistoryQuery) -> Result<Vec<Fill>> {
+        let url = history_url(&self.base_url, "/wapi/v1/history/fills", query);
+        let res = self.get(url).await?;
+        res.json().await.map_err(Into::into)
+    }
+
+    /// Streams the fills matching `query`, newest first, paging through `get_fill_history`.
+    /// The paging fields of `query` are replaced by those of `options`.
+    pub fn fill_history_stream<'a>(
+        &'a self,
+        query: &'a HistoryQuery,
+        options: PageOptions,
+    ) -> impl Stream<Item = Result<Paged<Fill>>> + 'a {
+        paginate(options, move |limit, offset| async move {
+            let query = HistoryQuery {
+                limit: Some(limit),
+                offset: Some(offset),
+                ..query.clone()
+            };
+            self.get_fill_history(&query).await
+        })
+    }
+}
+
+fn history_url(base_url: &str, path: &str, query: &HistoryQuery) -> String {
+    let mut url = format!("{base_url}{path}");
+    for (i, (k, v)) in query.params().into_iter().enumerate() {
+        let separator = if i == 0 { '?' } else { '&' };
+        url.push_str(&format!("{separator}{k}={v}"));
+    }
+    url
+}
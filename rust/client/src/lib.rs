@@ -3,15 +3,37 @@ use ed25519_dalek::{Signature, Signer, SigningKey, VerifyingKey};
 pub use error::{Error, Result};
 use reqwest::{header::CONTENT_TYPE, IntoUrl, Method, Request, Response};
 use serde::Serialize;
-use std::collections::BTreeMap;
+use serde_json::Value;
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use std::sync::{Arc, RwLock};
 
 pub use bpx_api_types as types;
+use bpx_api_types::markets::Market;
+use rate_limit::RateLimiter;
+use risk::RiskLimits;
+use withdrawal_policy::WithdrawalPolicy;
 
+pub mod balance_tracker;
 pub mod capital;
+pub mod deposit_watcher;
 pub mod error;
+pub mod history;
 pub mod markets;
 pub mod order;
+pub mod order_manager;
+pub mod pagination;
+pub mod pnl;
+pub mod portfolio;
+pub mod rate_limit;
+pub mod risk;
+pub mod statement;
+#[cfg(feature = "totp")]
+pub mod totp;
 pub mod trades;
+pub mod validation;
+pub mod withdrawal_plan;
+pub mod withdrawal_policy;
 
 const SIGNING_WINDOW: u32 = 5000;
 
@@ -21,6 +43,13 @@ pub struct BpxClient {
     signer: SigningKey,
     base_url: String,
     pub client: reqwest::Client,
+    markets: Arc<RwLock<HashMap<String, Market>>>,
+    validate_orders: bool,
+    rate_limiter: Option<Arc<RateLimiter>>,
+    withdrawal_policy: Option<Arc<WithdrawalPolicy>>,
+    risk_limits: Option<Arc<RiskLimits>>,
+    #[cfg(feature = "totp")]
+    totp: Option<Arc<totp::Totp>>,
 }
 
 impl std::ops::Deref for BpxClient {
@@ -67,9 +96,50 @@ impl BpxClient {
             signer,
             base_url,
             client,
+            markets: Arc::default(),
+            validate_orders: false,
+            rate_limiter: None,
+            withdrawal_policy: None,
+            risk_limits: None,
+            #[cfg(feature = "totp")]
+            totp: None,
         })
     }
 
+    /// Limits the client to `requests_per_second` requests, shared by all of its clones.
+    pub fn with_rate_limit(mut self, requests_per_second: u32) -> Self {
+        self.rate_limiter = Some(Arc::new(RateLimiter::per_second(requests_per_second)));
+        self
+    }
+
+    /// Checks every `execute_order` payload against the cached market filters before it is
+    /// signed and sent.
+    pub fn with_order_validation(mut self, enabled: bool) -> Self {
+        self.validate_orders = enabled;
+        self
+    }
+
+    /// Runs every `execute_order` and `execute_orders` payload through `limits` before it is
+    /// signed.
+    pub fn with_risk_limits(mut self, limits: RiskLimits) -> Self {
+        self.risk_limits = Some(Arc::new(limits));
+        self
+    }
+
+    /// Runs every `request_withdrawal` through `policy` before it is signed.
+    pub fn with_withdrawal_policy(mut self, policy: WithdrawalPolicy) -> Self {
+        self.withdrawal_policy = Some(Arc::new(policy));
+        self
+    }
+
+    /// Fills in `two_factor_token` on every `request_withdrawal` that doesn't carry one with a
+    /// code from `totp`. The seed stays private to the client, like the API secret.
+    #[cfg(feature = "totp")]
+    pub fn with_totp(mut self, totp: totp::Totp) -> Self {
+        self.totp = Some(Arc::new(totp));
+        self
+    }
+
     fn sign(&self, req: &mut Request) -> Result<()> {
         let instruction = match req.url().path() {
             "/api/v1/capital" if req.method() == Method::GET => "balanceQuery",
@@ -79,10 +149,13 @@ impl BpxClient {
             }
             "/wapi/v1/capital/withdrawals" if req.method() == Method::GET => "withdrawalQueryAll",
             "/wapi/v1/capital/withdrawals" if req.method() == Method::POST => "withdraw",
+            "/wapi/v1/history/orders" if req.method() == Method::GET => "orderHistoryQueryAll",
+            "/wapi/v1/history/fills" if req.method() == Method::GET => "fillHistoryQueryAll",
             "/api/v1/order" if req.method() == Method::GET => "orderQuery",
             "/api/v1/order" if req.method() == Method::POST => "orderExecute",
             "/api/v1/order" if req.method() == Method::DELETE => "orderCancel",
             "/api/v1/orders" if req.method() == Method::GET => "orderQueryAll",
+            "/api/v1/orders" if req.method() == Method::POST => "orderExecute",
             "/api/v1/orders" if req.method() == Method::DELETE => "orderCancelAll",
             _ => return Ok(()), // other endpoints don't require signing
         };
@@ -97,21 +170,22 @@ impl BpxClient {
             .map(|(x, y)| (x.into_owned(), y.into_owned()))
             .collect::<BTreeMap<String, String>>();
 
-        let body_params = if let Some(b) = req.body() {
+        let body = if let Some(b) = req.body() {
             let s = std::str::from_utf8(b.as_bytes().unwrap_or_default())?;
-            serde_json::from_str::<BTreeMap<String, String>>(s)?
+            serde_json::from_str::<Value>(s)?
         } else {
-            BTreeMap::new()
+            Value::Null
         };
 
-        let mut signee = format!("instruction={instruction}");
-        for (k, v) in query_params {
-            signee.push_str(&format!("&{k}={v}"));
-        }
-
-        for (k, v) in body_params {
-            signee.push_str(&format!("&{k}={v}"));
-        }
+        // Batch requests sign one segment per item, each with its own instruction prefix.
+        let mut signee = match &body {
+            Value::Array(items) => items
+                .iter()
+                .map(|item| signing_segment(instruction, &BTreeMap::new(), item))
+                .collect::<Vec<_>>()
+                .join("&"),
+            body => signing_segment(instruction, &query_params, body),
+        };
         signee.push_str(&format!("&timestamp={timestamp}&window={SIGNING_WINDOW}"));
         tracing::debug!("signee: {}", signee);
 
@@ -132,24 +206,63 @@ impl BpxClient {
         Ok(())
     }
 
+    async fn send(&self, req: Request) -> Result<Response> {
+        if let Some(rate_limiter) = &self.rate_limiter {
+            rate_limiter.acquire().await;
+        }
+        self.client.execute(req).await.map_err(Error::from)
+    }
+
     pub async fn get<U: IntoUrl>(&self, url: U) -> Result<Response> {
         let mut req = self.client.get(url).build()?;
         tracing::debug!("req: {:?}", req);
         self.sign(&mut req)?;
-        self.client.execute(req).await.map_err(Error::from)
+        self.send(req).await
     }
 
     pub async fn post<P: Serialize, U: IntoUrl>(&self, url: U, payload: P) -> Result<Response> {
         let mut req = self.client.post(url).json(&payload).build()?;
         tracing::debug!("req: {:?}", req);
         self.sign(&mut req)?;
-        self.client.execute(req).await.map_err(Error::from)
+        self.send(req).await
     }
 
     pub async fn delete<P: Serialize, U: IntoUrl>(&self, url: U, payload: P) -> Result<Response> {
         let mut req = self.client.delete(url).json(&payload).build()?;
         tracing::debug!("req: {:?}", req);
         self.sign(&mut req)?;
-        self.client.execute(req).await.map_err(Error::from)
+        self.send(req).await
+    }
+}
+
+/// Replaces the file at `path` with `contents` through a temporary file and a rename, so that a
+/// crash mid-write leaves either the old or the new contents.
+pub(crate) fn write_atomically(path: &Path, contents: &str) -> Result<()> {
+    let mut temp = path.as_os_str().to_owned();
+    temp.push(".tmp");
+    std::fs::write(&temp, contents)?;
+    std::fs::rename(&temp, path)?;
+    Ok(())
+}
+
+fn signing_segment(
+    instruction: &str,
+    query_params: &BTreeMap<String, String>,
+    body: &Value,
+) -> String {
+    let mut segment = format!("instruction={instruction}");
+    for (k, v) in query_params {
+        segment.push_str(&format!("&{k}={v}"));
+    }
+
+    if let Value::Object(fields) = body {
+        let fields = fields.iter().collect::<BTreeMap<_, _>>();
+        for (k, v) in fields {
+            match v {
+                Value::String(v) => segment.push_str(&format!("&{k}={v}")),
+                v => segment.push_str(&format!("&{k}={v}")),
+            }
+        }
     }
+    segment
 }
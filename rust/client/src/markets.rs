@@ -1,10 +1,17 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-use bpx_api_types::markets::{Kline, Market, OrderBookDepth, Ticker, Token};
+use bpx_api_types::markets::{Kline, KlineInterval, Market, OrderBookDepth, Ticker, Token};
+use chrono::{DateTime, Utc};
+use futures::{stream, Stream, StreamExt};
 
-use crate::error::Result;
+use crate::error::{Error, Result};
 use crate::BpxClient;
 
+/// Candles requested per `get_k_lines` call during a backfill.
+const BACKFILL_CHUNK_SIZE: usize = 500;
+/// `get_k_lines` calls a backfill keeps in flight at once.
+const BACKFILL_CONCURRENCY: usize = 4;
+
 impl BpxClient {
     pub async fn get_assets(&self) -> Result<HashMap<String, Vec<Token>>> {
         let url = format!("{}/api/v1/assets", self.base_url);
@@ -18,35 +25,141 @@ impl BpxClient {
         res.json().await.map_err(Into::into)
     }
 
-    pub async fn get_ticker(&self, symbol: &str) -> Result<Vec<Ticker>> {
-        let url = format!("{}/api/v1/ticker&symbol={}", self.base_url, symbol);
+    /// Returns the market for `symbol` from the local cache, loading every market on a miss.
+    pub async fn get_market_cached(&self, symbol: &str) -> Result<Option<Market>> {
+        if let Some(market) = self.cached_market(symbol) {
+            return Ok(Some(market));
+        }
+        self.refresh_markets().await?;
+        Ok(self.cached_market(symbol))
+    }
+
+    pub async fn refresh_markets(&self) -> Result<()> {
+        let markets = self.get_markets().await?;
+        let mut cache = self.markets.write().unwrap_or_else(|e| e.into_inner());
+        cache.clear();
+        cache.extend(markets.into_iter().map(|m| (m.symbol.clone(), m)));
+        Ok(())
+    }
+
+    fn cached_market(&self, symbol: &str) -> Option<Market> {
+        let cache = self.markets.read().unwrap_or_else(|e| e.into_inner());
+        cache.get(symbol).cloned()
+    }
+
+    pub async fn get_ticker(&self, symbol: &str) -> Result<Ticker> {
+        let url = format!("{}/api/v1/ticker?symbol={}", self.base_url, symbol);
+        let res = self.get(url).await?;
+        res.json().await.map_err(Into::into)
+    }
+
+    pub async fn get_tickers(&self) -> Result<Vec<Ticker>> {
+        let url = format!("{}/api/v1/tickers", self.base_url);
         let res = self.get(url).await?;
         res.json().await.map_err(Into::into)
     }
 
     pub async fn get_order_book_depth(&self, symbol: &str) -> Result<OrderBookDepth> {
-        let url = format!("{}/api/v1/depth&symbol={}", self.base_url, symbol);
+        let url = format!("{}/api/v1/depth?symbol={}", self.base_url, symbol);
         let res = self.get(url).await?;
         res.json().await.map_err(Into::into)
     }
 
+    /// Fails with `Error::InconsistentKline` if a candle's high and low don't bound its open
+    /// and close.
     pub async fn get_k_lines(
         &self,
         symbol: &str,
-        kline_interval: &str,
-        start_time: Option<i64>,
-        end_time: Option<i64>,
+        interval: KlineInterval,
+        start_time: Option<DateTime<Utc>>,
+        end_time: Option<DateTime<Utc>>,
     ) -> Result<Vec<Kline>> {
         let mut url = format!(
-            "/{}/api/v1/klines?symbol={}&kline_interval={}",
-            self.base_url, symbol, kline_interval
+            "{}/api/v1/klines?symbol={}&interval={}",
+            self.base_url, symbol, interval
         );
-        for (k, v) in [("start_time", start_time), ("end_time", end_time)] {
+        for (k, v) in [("startTime", start_time), ("endTime", end_time)] {
             if let Some(v) = v {
-                url.push_str(&format!("&{}={}", k, v));
+                url.push_str(&format!("&{}={}", k, v.timestamp()));
             }
         }
         let res = self.get(url).await?;
-        res.json().await.map_err(Into::into)
+        let klines: Vec<Kline> = res.json().await?;
+        if let Some(kline) = klines.iter().find(|kline| !kline.is_consistent()) {
+            return Err(Error::InconsistentKline {
+                symbol: symbol.to_string(),
+                start: kline.start,
+            });
+        }
+        Ok(klines)
+    }
+
+    /// Streams every candle of `interval` starting in `[from, to)`, oldest first. The range is
+    /// fetched in chunks with bounded concurrency; candles returned twice are de-duplicated and
+    /// intervals the exchange has no candle for are yielded as `Kline::empty`. A chunk with an
+    /// inconsistent candle yields `Error::InconsistentKline` in its place.
+    pub fn backfill_klines<'a>(
+        &'a self,
+        symbol: &'a str,
+        interval: KlineInterval,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+    ) -> impl Stream<Item = Result<Kline>> + 'a {
+        stream::iter(backfill_chunks(interval, from, to))
+            .map(move |starts| async move {
+                let first = starts[0];
+                let end = interval.next(starts[starts.len() - 1]);
+                let klines = self
+                    .get_k_lines(symbol, interval, Some(first), Some(end))
+                    .await?;
+                Ok(fill_gaps(interval, &starts, klines))
+            })
+            .buffered(BACKFILL_CONCURRENCY)
+            .flat_map(|chunk: Result<Vec<Kline>>| {
+                let items: Vec<Result<Kline>> = match chunk {
+                    Ok(klines) => klines.into_iter().map(Ok).collect(),
+                    Err(e) => vec![Err(e)],
+                };
+                stream::iter(items)
+            })
+    }
+}
+
+/// Splits `[from, to)` into request-sized runs of candle start times.
+fn backfill_chunks(
+    interval: KlineInterval,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Vec<Vec<DateTime<Utc>>> {
+    let mut chunks = Vec::new();
+    let mut chunk = Vec::with_capacity(BACKFILL_CHUNK_SIZE);
+    // The candle containing `from` starts before it unless `from` is on a boundary.
+    let mut start = interval.align(from);
+    if start < from {
+        start = interval.next(start);
+    }
+    while start < to {
+        chunk.push(start);
+        if chunk.len() == BACKFILL_CHUNK_SIZE {
+            chunks.push(std::mem::take(&mut chunk));
+        }
+        start = interval.next(start);
     }
+    if !chunk.is_empty() {
+        chunks.push(chunk);
+    }
+    chunks
+}
+
+/// Lays `klines` out on `starts`, keeping one candle per start and filling the holes.
+fn fill_gaps(interval: KlineInterval, starts: &[DateTime<Utc>], klines: Vec<Kline>) -> Vec<Kline> {
+    let mut by_start: BTreeMap<_, _> = klines.into_iter().map(|k| (k.start, k)).collect();
+    starts
+        .iter()
+        .map(|start| {
+            by_start
+                .remove(start)
+                .unwrap_or_else(|| Kline::empty(*start, interval.next(*start)))
+        })
+        .collect()
 }
@@ -1,10 +1,51 @@
-use bpx_api_types::order::{
-    CancelOpenOrdersPayload, CancelOrderPayload, ExecuteOrderPayload, Order,
+use bpx_api_types::{
+    history::HistoryQuery,
+    order::{
+        CancelOpenOrdersPayload, CancelOrderPayload, ExecuteOrderPayload, Order, OrderStatus,
+        OrderType,
+    },
 };
+use futures::{future::join_all, stream, StreamExt};
+use rust_decimal::Decimal;
+use serde::Deserialize;
+use std::collections::{BTreeMap, BTreeSet};
+use std::time::Duration;
 
-use crate::error::{Error, Result};
+use crate::error::{error_for_status, ApiError, Error, Result};
+use crate::validation::{self, ValidationError, Violation};
 use crate::BpxClient;
 
+/// Cancels a bulk cancellation keeps in flight at once.
+const CANCEL_CONCURRENCY: usize = 8;
+/// How often `wait_for_order` polls the order's state.
+const ORDER_POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The result of `BpxClient::replace_order`.
+#[derive(Debug)]
+pub enum ReplaceOutcome {
+    /// The old order was cancelled and the replacement accepted. `cancelled` carries whatever
+    /// the old order executed before the cancel.
+    Replaced {
+        cancelled: Order,
+        replacement: Box<Order>,
+    },
+    /// The old order filled completely before it could be cancelled, so nothing was placed.
+    OldFilled { order: Order },
+    /// The old order was cancelled after executing at least `new_quantity`, so nothing was
+    /// left to place.
+    NothingLeft { cancelled: Order },
+    /// The old order was cancelled but the exchange refused the replacement; the quote is no
+    /// longer on the book.
+    ReplaceRejected { cancelled: Order, error: Error },
+}
+
+/// The result of cancelling one order selected by `BpxClient::cancel_orders_where`.
+#[derive(Debug)]
+pub struct CancelOutcome {
+    pub order: Order,
+    pub result: Result<Order>,
+}
+
 impl BpxClient {
     pub async fn get_open_order(
         &self,
@@ -23,16 +64,67 @@ impl BpxClient {
                 ))?
             ));
         }
-        let res = self.get(url).await?;
+        let res = error_for_status(self.get(url).await?).await?;
         res.json().await.map_err(Into::into)
     }
 
     pub async fn execute_order(&self, payload: ExecuteOrderPayload) -> Result<Order> {
+        if self.validate_orders {
+            self.validate_order(&payload).await?;
+        }
+        self.check_risk(&payload).await?;
         let endpoint = format!("{}/api/v1/order", self.base_url);
-        let res = self.post(endpoint, payload).await?;
+        let res = error_for_status(self.post(endpoint, payload).await?).await?;
         res.json().await.map_err(Into::into)
     }
 
+    /// Places several orders in one signed request. Each order is accepted or rejected on its
+    /// own, so the results line up with `payloads`.
+    pub async fn execute_orders(
+        &self,
+        payloads: Vec<ExecuteOrderPayload>,
+    ) -> Result<Vec<std::result::Result<Order, ApiError>>> {
+        if payloads.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.validate_orders {
+            for payload in &payloads {
+                self.validate_order(payload).await?;
+            }
+        }
+        self.check_risk_batch(&payloads).await?;
+
+        #[derive(Deserialize)]
+        #[serde(untagged)]
+        enum BatchEntry {
+            Order(Box<Order>),
+            Error(ApiError),
+        }
+
+        let endpoint = format!("{}/api/v1/orders", self.base_url);
+        let res = error_for_status(self.post(endpoint, payloads).await?).await?;
+        let entries: Vec<BatchEntry> = res.json().await?;
+        Ok(entries
+            .into_iter()
+            .map(|entry| match entry {
+                BatchEntry::Order(order) => Ok(*order),
+                BatchEntry::Error(e) => Err(e),
+            })
+            .collect())
+    }
+
+    /// Validates `payload` against the filters of its market without sending it.
+    pub async fn validate_order(&self, payload: &ExecuteOrderPayload) -> Result<()> {
+        let market = self
+            .get_market_cached(&payload.symbol)
+            .await?
+            .ok_or_else(|| ValidationError {
+                symbol: payload.symbol.clone(),
+                violations: vec![Violation::UnknownMarket(payload.symbol.clone())],
+            })?;
+        validation::validate_order(payload, &market).map_err(Into::into)
+    }
+
     pub async fn cancel_order(
         &self,
         symbol: &str,
@@ -50,6 +142,16 @@ impl BpxClient {
         res.json().await.map_err(Into::into)
     }
 
+    /// Cancels each of `order_ids` on `symbol`, returning one result per id in the same order.
+    pub async fn cancel_orders(&self, symbol: &str, order_ids: &[&str]) -> Vec<Result<Order>> {
+        join_all(
+            order_ids
+                .iter()
+                .map(|order_id| self.cancel_order(symbol, Some(order_id), None)),
+        )
+        .await
+    }
+
     pub async fn get_open_orders(&self, symbol: Option<&str>) -> Result<Vec<Order>> {
         let mut url = format!("{}/api/v1/orders", self.base_url);
         if let Some(s) = symbol {
@@ -64,4 +166,172 @@ impl BpxClient {
         let res = self.delete(url, payload).await?;
         res.json().await.map_err(Into::into)
     }
+
+    /// Cancels the resting limit order `existing` and places a new one at `new_price`.
+    ///
+    /// `new_quantity` is the total size wanted, so whatever the old order already executed is
+    /// subtracted from it. The replacement keeps the old order's side, time in force,
+    /// self-trade prevention, post-only flag and `client_id`.
+    pub async fn replace_order(
+        &self,
+        existing: &Order,
+        new_price: Decimal,
+        new_quantity: Decimal,
+    ) -> Result<ReplaceOutcome> {
+        let Order::Limit(limit) = existing else {
+            return Err(Error::InvalidRequest(
+                "only limit orders can be replaced".to_string(),
+            ));
+        };
+
+        let cancelled = match self
+            .cancel_order(&limit.symbol, Some(&limit.id), None)
+            .await
+        {
+            Ok(cancelled) => cancelled,
+            Err(e) => {
+                // The cancel also fails when the order filled in the meantime.
+                let query = HistoryQuery {
+                    symbol: Some(limit.symbol.clone()),
+                    order_id: Some(limit.id.clone()),
+                    ..Default::default()
+                };
+                let history = self.get_order_history(&query).await?;
+                return match history.into_iter().find(|o| o.id() == limit.id) {
+                    Some(order) if order.status() == OrderStatus::Filled => {
+                        Ok(ReplaceOutcome::OldFilled { order })
+                    }
+                    _ => Err(e),
+                };
+            }
+        };
+
+        if cancelled.status() == OrderStatus::Filled {
+            return Ok(ReplaceOutcome::OldFilled { order: cancelled });
+        }
+        let remaining = new_quantity - cancelled.executed_quantity();
+        if remaining <= Decimal::ZERO {
+            return Ok(ReplaceOutcome::NothingLeft { cancelled });
+        }
+
+        let payload = ExecuteOrderPayload {
+            client_id: limit.client_id,
+            order_type: OrderType::Limit,
+            post_only: Some(limit.post_only),
+            price: Some(new_price),
+            quantity: Some(remaining),
+            self_trade_prevention: Some(limit.self_trade_prevention.clone()),
+            side: limit.side.clone(),
+            symbol: limit.symbol.clone(),
+            time_in_force: Some(limit.time_in_force.clone()),
+            ..Default::default()
+        };
+
+        Ok(match self.execute_order(payload).await {
+            Ok(replacement) => ReplaceOutcome::Replaced {
+                cancelled,
+                replacement: Box::new(replacement),
+            },
+            Err(error) => ReplaceOutcome::ReplaceRejected { cancelled, error },
+        })
+    }
+
+    /// Cancels the open orders, on `symbol` or on every market, for which `predicate` returns
+    /// true, e.g. only bids or only orders outside a price band. Cancels run concurrently and
+    /// each selected order gets its own outcome.
+    pub async fn cancel_orders_where<F>(
+        &self,
+        symbol: Option<&str>,
+        predicate: F,
+    ) -> Result<Vec<CancelOutcome>>
+    where
+        F: Fn(&Order) -> bool,
+    {
+        let orders = self.get_open_orders(symbol).await?;
+        let outcomes = stream::iter(orders.into_iter().filter(|order| predicate(order)))
+            .map(|order| async move {
+                let result = self
+                    .cancel_order(order.symbol(), Some(order.id()), None)
+                    .await;
+                CancelOutcome { order, result }
+            })
+            .buffer_unordered(CANCEL_CONCURRENCY)
+            .collect()
+            .await;
+        Ok(outcomes)
+    }
+
+    /// Cancels every open order on every market that has one, keyed by symbol.
+    pub async fn cancel_all_everywhere(&self) -> Result<BTreeMap<String, Result<Vec<Order>>>> {
+        let symbols = self
+            .get_open_orders(None)
+            .await?
+            .iter()
+            .map(|order| order.symbol().to_string())
+            .collect::<BTreeSet<_>>();
+
+        let results = stream::iter(symbols)
+            .map(|symbol| async move {
+                let payload = CancelOpenOrdersPayload {
+                    symbol: symbol.clone(),
+                };
+                (symbol, self.cancel_open_orders(payload).await)
+            })
+            .buffer_unordered(CANCEL_CONCURRENCY)
+            .collect()
+            .await;
+        Ok(results)
+    }
+
+    /// Waits until order `order_id` reaches one of the `until` statuses or any terminal status,
+    /// and returns it. The client has no private stream, so the order is polled through
+    /// `get_open_order`, falling back to the order history once it has left the book.
+    ///
+    /// Failed polls are logged and retried. On timeout, `Error::OrderTimeout` carries the last
+    /// state that was seen.
+    pub async fn wait_for_order(
+        &self,
+        symbol: &str,
+        order_id: &str,
+        until: &[OrderStatus],
+        timeout: Duration,
+    ) -> Result<Order> {
+        let mut last_known = None;
+        let wait = async {
+            loop {
+                match self.poll_order(symbol, order_id).await {
+                    Ok(Some(order)) => {
+                        if until.contains(&order.status()) || order.is_terminal() {
+                            return order;
+                        }
+                        last_known = Some(Box::new(order));
+                    }
+                    Ok(None) => {}
+                    Err(e) => tracing::warn!(order_id, "polling order failed: {e}"),
+                }
+                tokio::time::sleep(ORDER_POLL_INTERVAL).await;
+            }
+        };
+
+        match tokio::time::timeout(timeout, wait).await {
+            Ok(order) => Ok(order),
+            Err(_) => Err(Error::OrderTimeout { last_known }),
+        }
+    }
+
+    /// The order from the open orders or, once the exchange says it isn't open, the history.
+    async fn poll_order(&self, symbol: &str, order_id: &str) -> Result<Option<Order>> {
+        match self.get_open_order(symbol, Some(order_id), None).await {
+            Ok(order) => return Ok(Some(order)),
+            Err(e) if e.is_refusal() => {}
+            Err(e) => return Err(e),
+        }
+        let query = HistoryQuery {
+            symbol: Some(symbol.to_string()),
+            order_id: Some(order_id.to_string()),
+            ..Default::default()
+        };
+        let history = self.get_order_history(&query).await?;
+        Ok(history.into_iter().find(|order| order.id() == order_id))
+    }
 }
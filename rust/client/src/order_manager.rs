@@ -0,0 +1,458 @@
+use std::collections::HashSet;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+
+use bpx_api_types::{
+    history::HistoryQuery,
+    order::{ExecuteOrderPayload, Order, OrderStatus, Side},
+};
+use chrono::Utc;
+use rust_decimal::Decimal;
+
+use crate::error::{Error, Result};
+use crate::BpxClient;
+
+/// How far ahead of the exchange's clock the local one may be when matching history to an
+/// order whose submission had an unknown outcome.
+const CLOCK_SKEW_MILLIS: i64 = 5_000;
+
+/// The local lifecycle of an order. States only move forward, and the last three are final,
+/// except that an exchange update can still revive a `Rejected` order the exchange accepted.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OrderState {
+    PendingNew,
+    New,
+    PartiallyFilled,
+    Filled,
+    Cancelled,
+    Rejected,
+}
+
+impl OrderState {
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self,
+            OrderState::PendingNew | OrderState::New | OrderState::PartiallyFilled
+        )
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        !self.is_open()
+    }
+
+    fn rank(&self) -> u8 {
+        match self {
+            OrderState::PendingNew => 0,
+            OrderState::New => 1,
+            OrderState::PartiallyFilled => 2,
+            OrderState::Filled | OrderState::Cancelled | OrderState::Rejected => 3,
+        }
+    }
+
+    pub fn can_transition_to(&self, next: OrderState) -> bool {
+        match (self, next) {
+            (OrderState::PendingNew, OrderState::Rejected) => true,
+            (_, OrderState::Rejected) => false,
+            (
+                OrderState::Rejected,
+                OrderState::New | OrderState::PartiallyFilled | OrderState::Filled,
+            ) => true,
+            (current, next) if current.is_terminal() => *current == next,
+            (current, next) => next.rank() >= current.rank(),
+        }
+    }
+
+    /// Maps an exchange status onto the local state machine. Statuses this crate doesn't know
+    /// return `None` and leave the local state untouched.
+    fn from_exchange(status: &OrderStatus, executed_quantity: Decimal) -> Option<OrderState> {
+        Some(match status {
+            OrderStatus::New | OrderStatus::Triggered if executed_quantity > Decimal::ZERO => {
+                OrderState::PartiallyFilled
+            }
+            OrderStatus::New | OrderStatus::Triggered => OrderState::New,
+            OrderStatus::PartiallyFilled => OrderState::PartiallyFilled,
+            OrderStatus::Filled => OrderState::Filled,
+            OrderStatus::Cancelled | OrderStatus::Expired => OrderState::Cancelled,
+            OrderStatus::Unknown(_) => return None,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct TrackedOrder {
+    /// Unset until the exchange has acknowledged the order.
+    pub order_id: Option<String>,
+    pub client_id: Option<u32>,
+    pub symbol: String,
+    pub side: Side,
+    pub price: Option<Decimal>,
+    pub quantity: Option<Decimal>,
+    pub executed_quantity: Decimal,
+    pub executed_quote_quantity: Decimal,
+    pub state: OrderState,
+    /// Why the order was rejected, when it was.
+    pub reject_reason: Option<String>,
+    /// When `submit` sent the order, in milliseconds since the epoch. Unset for orders placed
+    /// outside the manager.
+    pub submitted_at: Option<i64>,
+}
+
+impl TrackedOrder {
+    pub fn remaining_quantity(&self) -> Option<Decimal> {
+        self.quantity
+            .map(|quantity| (quantity - self.executed_quantity).max(Decimal::ZERO))
+    }
+}
+
+/// Emitted whenever an update shows that more of an order has executed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FillEvent {
+    pub order_id: String,
+    pub client_id: Option<u32>,
+    pub symbol: String,
+    pub side: Side,
+    /// The quantity executed since the previous update.
+    pub quantity: Decimal,
+    /// The quote quantity executed since the previous update.
+    pub quote_quantity: Decimal,
+    pub executed_quantity: Decimal,
+    pub state: OrderState,
+}
+
+/// The resting size of the open orders on one symbol.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Exposure {
+    pub bid_quantity: Decimal,
+    pub ask_quantity: Decimal,
+    pub bid_notional: Decimal,
+    pub ask_notional: Decimal,
+}
+
+/// An order update fed to `OrderManager::apply`, e.g. from a private order stream.
+#[derive(Debug, Clone)]
+pub enum OrderEvent {
+    Update(Box<Order>),
+    Rejected { client_id: u32, reason: String },
+}
+
+#[derive(Debug, Default)]
+struct Book {
+    orders: Vec<TrackedOrder>,
+    fills: Vec<FillEvent>,
+    next_client_id: u32,
+}
+
+impl Book {
+    fn find(&mut self, order_id: &str, client_id: Option<u32>) -> Option<&mut TrackedOrder> {
+        let index = self.orders.iter().position(|tracked| {
+            tracked.order_id.as_deref() == Some(order_id)
+                || (tracked.order_id.is_none()
+                    && client_id.is_some()
+                    && tracked.client_id == client_id)
+        })?;
+        Some(&mut self.orders[index])
+    }
+
+    fn apply(&mut self, order: &Order) {
+        let Some(next) = OrderState::from_exchange(&order.status(), order.executed_quantity())
+        else {
+            tracing::warn!(
+                order_id = order.id(),
+                status = %order.status(),
+                "unknown order status"
+            );
+            return;
+        };
+
+        let Some(tracked) = self.find(order.id(), order.client_id()) else {
+            // Placed outside the manager, or acknowledged before `submit` returned.
+            self.orders.push(TrackedOrder {
+                order_id: Some(order.id().to_string()),
+                client_id: order.client_id(),
+                symbol: order.symbol().to_string(),
+                side: order.side(),
+                price: order.price(),
+                quantity: order.quantity(),
+                executed_quantity: order.executed_quantity(),
+                executed_quote_quantity: order.executed_quote_quantity(),
+                state: next,
+                reject_reason: None,
+                submitted_at: None,
+            });
+            return;
+        };
+
+        // Updates can arrive out of order; never roll back state or executed quantity.
+        if !tracked.state.can_transition_to(next)
+            || order.executed_quantity() < tracked.executed_quantity
+        {
+            tracing::debug!(
+                order_id = order.id(),
+                from = ?tracked.state,
+                to = ?next,
+                "stale order update"
+            );
+            return;
+        }
+
+        let filled = order.executed_quantity() - tracked.executed_quantity;
+        let filled_quote = order.executed_quote_quantity() - tracked.executed_quote_quantity;
+        tracked.order_id = Some(order.id().to_string());
+        tracked.executed_quantity = order.executed_quantity();
+        tracked.executed_quote_quantity = order.executed_quote_quantity();
+        tracked.state = next;
+        tracked.reject_reason = None;
+
+        if filled > Decimal::ZERO {
+            let event = FillEvent {
+                order_id: order.id().to_string(),
+                client_id: tracked.client_id,
+                symbol: tracked.symbol.clone(),
+                side: tracked.side.clone(),
+                quantity: filled,
+                quote_quantity: filled_quote,
+                executed_quantity: tracked.executed_quantity,
+                state: next,
+            };
+            self.fills.push(event);
+        }
+    }
+
+    fn reject(&mut self, client_id: u32, reason: String) {
+        let tracked = self
+            .orders
+            .iter_mut()
+            .find(|tracked| tracked.order_id.is_none() && tracked.client_id == Some(client_id));
+        if let Some(tracked) = tracked {
+            if tracked.state.can_transition_to(OrderState::Rejected) {
+                tracked.state = OrderState::Rejected;
+                tracked.reject_reason = Some(reason);
+            }
+        }
+    }
+}
+
+/// Tracks orders placed through it in a local state machine, kept in line with the exchange
+/// by the updates passed to `apply` and by periodic `reconcile` calls.
+#[derive(Debug, Clone)]
+pub struct OrderManager {
+    client: BpxClient,
+    book: Arc<Mutex<Book>>,
+}
+
+impl OrderManager {
+    pub fn new(client: BpxClient) -> Self {
+        // Seed client ids from the clock in tenths of a second, so that a restarted manager
+        // only reuses ids if the last one averaged over ten orders a second. `reconcile` also
+        // ignores history older than the submission, in case it did.
+        let seed = (Utc::now().timestamp_millis() / 100) as u32;
+        let book = Book {
+            next_client_id: seed,
+            ..Default::default()
+        };
+        Self {
+            client,
+            book: Arc::new(Mutex::new(book)),
+        }
+    }
+
+    fn book(&self) -> MutexGuard<'_, Book> {
+        self.book.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Places `payload`, tracking it as `PendingNew` until the exchange answers. Orders without
+    /// a `client_id` are given one.
+    ///
+    /// Only a refusal, by the local checks or by the exchange with a 4xx, marks the order
+    /// `Rejected`. After any other failure, including a 5xx or a rate limit, the exchange may still have accepted it, so it stays `PendingNew`
+    /// until `reconcile` finds out.
+    pub async fn submit(&self, mut payload: ExecuteOrderPayload) -> Result<Order> {
+        let client_id = {
+            let mut book = self.book();
+            let client_id = payload.client_id.unwrap_or_else(|| {
+                book.next_client_id = book.next_client_id.wrapping_add(1);
+                book.next_client_id
+            });
+            book.orders.push(TrackedOrder {
+                order_id: None,
+                client_id: Some(client_id),
+                symbol: payload.symbol.clone(),
+                side: payload.side.clone(),
+                price: payload.price,
+                quantity: payload.quantity,
+                executed_quantity: Decimal::ZERO,
+                executed_quote_quantity: Decimal::ZERO,
+                state: OrderState::PendingNew,
+                reject_reason: None,
+                submitted_at: Some(Utc::now().timestamp_millis()),
+            });
+            client_id
+        };
+        payload.client_id = Some(client_id);
+
+        match self.client.execute_order(payload).await {
+            Ok(order) => {
+                self.book().apply(&order);
+                Ok(order)
+            }
+            Err(e)
+                if e.is_refusal()
+                    || matches!(e, Error::Validation(_) | Error::RiskRejected { .. }) =>
+            {
+                self.book().reject(client_id, e.to_string());
+                Err(e)
+            }
+            Err(e) => {
+                tracing::warn!(client_id, "order outcome unknown: {e}");
+                Err(e)
+            }
+        }
+    }
+
+    pub async fn cancel(&self, order_id: &str) -> Result<Order> {
+        let symbol = self
+            .order(order_id)
+            .map(|tracked| tracked.symbol)
+            .ok_or_else(|| Error::InvalidRequest(format!("untracked order {order_id}")))?;
+        let order = self
+            .client
+            .cancel_order(&symbol, Some(order_id), None)
+            .await?;
+        self.book().apply(&order);
+        Ok(order)
+    }
+
+    pub fn apply(&self, event: OrderEvent) {
+        let mut book = self.book();
+        match event {
+            OrderEvent::Update(order) => book.apply(&order),
+            OrderEvent::Rejected { client_id, reason } => book.reject(client_id, reason),
+        }
+    }
+
+    /// Brings the local state in line with the exchange. Open orders are refreshed from
+    /// `get_open_orders`; tracked orders that are no longer open, or whose submission had an
+    /// unknown outcome, are looked up in the order history to learn how they ended.
+    pub async fn reconcile(&self) -> Result<()> {
+        let open = self.client.get_open_orders(None).await?;
+        let open_ids = open
+            .iter()
+            .map(|order| order.id().to_string())
+            .collect::<HashSet<_>>();
+
+        let missing = {
+            let mut book = self.book();
+            for order in &open {
+                book.apply(order);
+            }
+            book.orders
+                .iter()
+                .filter(|tracked| tracked.state.is_open())
+                .filter(|tracked| {
+                    tracked
+                        .order_id
+                        .as_ref()
+                        .is_none_or(|order_id| !open_ids.contains(order_id))
+                })
+                .map(|tracked| {
+                    (
+                        tracked.symbol.clone(),
+                        tracked.order_id.clone(),
+                        tracked.client_id,
+                        tracked.submitted_at,
+                    )
+                })
+                .collect::<Vec<_>>()
+        };
+
+        for (symbol, order_id, client_id, submitted_at) in missing {
+            let query = HistoryQuery {
+                symbol: Some(symbol),
+                order_id: order_id.clone(),
+                ..Default::default()
+            };
+            let history = self.client.get_order_history(&query).await?;
+            let found = history.iter().find(|order| match &order_id {
+                Some(order_id) => order.id() == order_id,
+                None => {
+                    client_id.is_some()
+                        && order.client_id() == client_id
+                        && submitted_at.is_none_or(|submitted_at| {
+                            order.created_at() >= submitted_at - CLOCK_SKEW_MILLIS
+                        })
+                }
+            });
+            match found {
+                Some(order) => self.book().apply(order),
+                None => tracing::warn!(
+                    order_id,
+                    client_id,
+                    "order is not open and has no history yet"
+                ),
+            }
+        }
+        Ok(())
+    }
+
+    /// Calls `reconcile` every `period`, logging failures. Runs until the future is dropped.
+    pub async fn run_reconciliation(&self, period: Duration) {
+        let mut interval = tokio::time::interval(period);
+        loop {
+            interval.tick().await;
+            if let Err(e) = self.reconcile().await {
+                tracing::warn!("order reconciliation failed: {e}");
+            }
+        }
+    }
+
+    pub fn order(&self, order_id: &str) -> Option<TrackedOrder> {
+        self.book()
+            .orders
+            .iter()
+            .find(|tracked| tracked.order_id.as_deref() == Some(order_id))
+            .cloned()
+    }
+
+    /// The open orders, on `symbol` or on every market.
+    pub fn open_orders(&self, symbol: Option<&str>) -> Vec<TrackedOrder> {
+        self.book()
+            .orders
+            .iter()
+            .filter(|tracked| tracked.state.is_open())
+            .filter(|tracked| symbol.is_none_or(|s| tracked.symbol == s))
+            .cloned()
+            .collect()
+    }
+
+    /// The unfilled size of the open orders on `symbol`. Notional is only counted for orders
+    /// with a price.
+    pub fn exposure(&self, symbol: &str) -> Exposure {
+        let mut exposure = Exposure::default();
+        for tracked in self.open_orders(Some(symbol)) {
+            let remaining = tracked.remaining_quantity().unwrap_or_default();
+            let notional = tracked.price.map_or(Decimal::ZERO, |p| p * remaining);
+            match tracked.side {
+                Side::Bid => {
+                    exposure.bid_quantity += remaining;
+                    exposure.bid_notional += notional;
+                }
+                Side::Ask => {
+                    exposure.ask_quantity += remaining;
+                    exposure.ask_notional += notional;
+                }
+                Side::Unknown(_) => {}
+            }
+        }
+        exposure
+    }
+
+    /// Returns and clears the fills seen since the last call.
+    pub fn drain_fills(&self) -> Vec<FillEvent> {
+        std::mem::take(&mut self.book().fills)
+    }
+
+    /// Forgets orders in a final state.
+    pub fn prune_terminal(&self) {
+        self.book().orders.retain(|tracked| tracked.state.is_open());
+    }
+}
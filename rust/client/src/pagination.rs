@@ -0,0 +1,222 @@
+use std::collections::VecDeque;
+use std::future::Future;
+
+use bpx_api_types::{
+    capital::{Deposit, Withdrawal},
+    history::Fill,
+    trade::Trade,
+};
+use chrono::{DateTime, TimeZone, Utc};
+use futures::{stream, Stream};
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+
+const DEFAULT_PAGE_SIZE: i64 = 100;
+
+/// Position in an offset/limit listing. Serialize it to resume a crawl later.
+///
+/// Listings are newest first, so items created after the cursor was taken push older ones to
+/// higher offsets. The offset is only where a resumed crawl starts looking; items at or above
+/// `last_timestamp` that were already yielded are recognised by their key and skipped. Items
+/// without a key can't be recognised, so those at `last_timestamp` may be yielded again.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Cursor {
+    pub offset: i64,
+    /// The timestamp of the last item yielded.
+    pub last_timestamp: Option<DateTime<Utc>>,
+    /// The keys of the items yielded with `last_timestamp`.
+    pub last_keys: Vec<String>,
+}
+
+impl Cursor {
+    /// Whether `item` was yielded before this cursor was taken.
+    fn covers<T: Timestamped>(&self, item: &T) -> bool {
+        let Some(last_timestamp) = self.last_timestamp else {
+            return false;
+        };
+        let timestamp = item.timestamp();
+        timestamp > last_timestamp
+            || (timestamp == last_timestamp
+                && item.key().is_some_and(|key| self.last_keys.contains(&key)))
+    }
+
+    fn advance<T: Timestamped>(&mut self, item: &T) {
+        self.offset += 1;
+        let timestamp = item.timestamp();
+        if self.last_timestamp != Some(timestamp) {
+            self.last_timestamp = Some(timestamp);
+            self.last_keys.clear();
+        }
+        self.last_keys.extend(item.key());
+    }
+}
+
+/// Controls how a `*_stream` call pages through an endpoint. Listings are returned newest
+/// first, so `since` ends the stream once older items appear while `until` only skips the
+/// items newer than it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PageOptions {
+    pub page_size: i64,
+    pub cursor: Cursor,
+    pub since: Option<DateTime<Utc>>,
+    pub until: Option<DateTime<Utc>>,
+}
+
+impl Default for PageOptions {
+    fn default() -> Self {
+        Self {
+            page_size: DEFAULT_PAGE_SIZE,
+            cursor: Cursor::default(),
+            since: None,
+            until: None,
+        }
+    }
+}
+
+impl PageOptions {
+    pub fn page_size(mut self, page_size: i64) -> Self {
+        self.page_size = page_size.max(1);
+        self
+    }
+
+    pub fn resume_from(mut self, cursor: Cursor) -> Self {
+        self.cursor = cursor;
+        self
+    }
+
+    pub fn since(mut self, since: DateTime<Utc>) -> Self {
+        self.since = Some(since);
+        self
+    }
+
+    pub fn until(mut self, until: DateTime<Utc>) -> Self {
+        self.until = Some(until);
+        self
+    }
+}
+
+/// An item yielded by a `*_stream` call, with the cursor that resumes right after it.
+#[derive(Debug, Clone)]
+pub struct Paged<T> {
+    pub item: T,
+    pub cursor: Cursor,
+}
+
+/// Items that carry the time used by `PageOptions::since` and `PageOptions::until`, and a key
+/// that tells them apart from other items with the same time.
+pub trait Timestamped {
+    fn timestamp(&self) -> DateTime<Utc>;
+
+    /// A unique id, or `None` if the item has none.
+    fn key(&self) -> Option<String>;
+}
+
+impl Timestamped for Deposit {
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.created_at.and_utc()
+    }
+
+    fn key(&self) -> Option<String> {
+        Some(self.id.to_string())
+    }
+}
+
+impl Timestamped for Withdrawal {
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.created_at.and_utc()
+    }
+
+    fn key(&self) -> Option<String> {
+        Some(self.id.to_string())
+    }
+}
+
+impl Timestamped for Fill {
+    fn timestamp(&self) -> DateTime<Utc> {
+        self.timestamp.and_utc()
+    }
+
+    fn key(&self) -> Option<String> {
+        // Equal fills of one order can share every other field, so only the trade id is unique.
+        self.trade_id.map(|trade_id| trade_id.to_string())
+    }
+}
+
+impl Timestamped for Trade {
+    fn timestamp(&self) -> DateTime<Utc> {
+        Utc.timestamp_millis_opt(self.timestamp)
+            .single()
+            .unwrap_or_default()
+    }
+
+    fn key(&self) -> Option<String> {
+        Some(self.id.to_string())
+    }
+}
+
+struct PageState<T, F> {
+    fetch: F,
+    options: PageOptions,
+    cursor: Cursor,
+    buffered: VecDeque<T>,
+    exhausted: bool,
+}
+
+/// Turns an offset/limit endpoint into a stream that pages until a short page is returned, an
+/// item falls before `options.since`, or a request fails. Items the cursor already covers, e.g.
+/// pushed onto the next page by newer activity, are skipped.
+pub(crate) fn paginate<'a, T, F, Fut>(
+    options: PageOptions,
+    fetch: F,
+) -> impl Stream<Item = Result<Paged<T>>> + 'a
+where
+    T: Timestamped + 'a,
+    F: Fn(i64, i64) -> Fut + 'a,
+    Fut: Future<Output = Result<Vec<T>>> + 'a,
+{
+    let state = PageState {
+        fetch,
+        cursor: options.cursor.clone(),
+        options,
+        buffered: VecDeque::<T>::new(),
+        exhausted: false,
+    };
+
+    stream::unfold(state, |mut state| async move {
+        loop {
+            if let Some(item) = state.buffered.pop_front() {
+                if state.cursor.covers(&item) {
+                    state.cursor.offset += 1;
+                    continue;
+                }
+                state.cursor.advance(&item);
+                let timestamp = item.timestamp();
+                if state.options.since.is_some_and(|since| timestamp < since) {
+                    return None;
+                }
+                if state.options.until.is_some_and(|until| timestamp > until) {
+                    continue;
+                }
+                let cursor = state.cursor.clone();
+                return Some((Ok(Paged { item, cursor }), state));
+            }
+
+            if state.exhausted {
+                return None;
+            }
+
+            let page_size = state.options.page_size;
+            match (state.fetch)(page_size, state.cursor.offset).await {
+                Ok(page) => {
+                    state.exhausted = (page.len() as i64) < page_size;
+                    state.buffered = page.into();
+                }
+                Err(e) => {
+                    state.exhausted = true;
+                    return Some((Err(e), state));
+                }
+            }
+        }
+    })
+}
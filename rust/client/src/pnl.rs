@@ -0,0 +1,465 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+use bpx_api_types::{history::Fill, order::Side, trade::Trade};
+use chrono::{DateTime, TimeZone, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Error, Result};
+
+/// How fills closing a position are matched against the lots that opened it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CostBasis {
+    Fifo,
+    Lifo,
+    AverageCost,
+}
+
+/// A fill as the PnL engine sees it, built from a `Fill` or a public `Trade`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PnlFill {
+    pub symbol: String,
+    pub side: Side,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub fee: Decimal,
+    pub fee_symbol: String,
+    pub timestamp: DateTime<Utc>,
+}
+
+impl From<&Fill> for PnlFill {
+    fn from(fill: &Fill) -> Self {
+        Self {
+            symbol: fill.symbol.clone(),
+            side: fill.side.clone(),
+            price: fill.price,
+            quantity: fill.quantity,
+            fee: fill.fee,
+            fee_symbol: fill.fee_symbol.clone(),
+            timestamp: fill.timestamp.and_utc(),
+        }
+    }
+}
+
+impl PnlFill {
+    /// A fee-free fill from a public trade; `side` is the side this account took.
+    pub fn from_trade(symbol: &str, side: Side, trade: &Trade) -> Self {
+        Self {
+            symbol: symbol.to_string(),
+            side,
+            price: trade.price,
+            quantity: trade.quantity,
+            fee: Decimal::ZERO,
+            fee_symbol: quote_symbol(symbol).to_string(),
+            timestamp: Utc
+                .timestamp_millis_opt(trade.timestamp)
+                .single()
+                .unwrap_or_default(),
+        }
+    }
+}
+
+fn split_symbol(symbol: &str) -> (&str, &str) {
+    symbol.split_once('_').unwrap_or((symbol, ""))
+}
+
+fn quote_symbol(symbol: &str) -> &str {
+    split_symbol(symbol).1
+}
+
+/// Part of a position opened at one price. Long lots have a positive quantity, short lots a
+/// negative one.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Lot {
+    pub quantity: Decimal,
+    pub price: Decimal,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Position {
+    pub lots: VecDeque<Lot>,
+    /// Realized PnL in the quote asset, before fees.
+    pub realized: Decimal,
+    /// Fees paid, converted to the quote asset.
+    pub fees: Decimal,
+}
+
+impl Position {
+    /// The net quantity held; negative when short.
+    pub fn quantity(&self) -> Decimal {
+        self.lots.iter().map(|lot| lot.quantity).sum()
+    }
+
+    /// The average entry price of the open lots.
+    pub fn average_cost(&self) -> Option<Decimal> {
+        let quantity = self.quantity();
+        if quantity.is_zero() {
+            return None;
+        }
+        let cost = self
+            .lots
+            .iter()
+            .map(|lot| lot.quantity * lot.price)
+            .sum::<Decimal>();
+        Some(cost / quantity)
+    }
+
+    /// The PnL of the open lots if they were closed at `mark`.
+    pub fn unrealized(&self, mark: Decimal) -> Decimal {
+        self.lots
+            .iter()
+            .map(|lot| (mark - lot.price) * lot.quantity)
+            .sum()
+    }
+
+    fn apply(&mut self, method: CostBasis, side: &Side, price: Decimal, quantity: Decimal) {
+        let mut remaining = match side {
+            Side::Bid => quantity,
+            Side::Ask => -quantity,
+            Side::Unknown(_) => return,
+        };
+
+        while !remaining.is_zero() {
+            let lot = match method {
+                CostBasis::Fifo | CostBasis::AverageCost => self.lots.front_mut(),
+                CostBasis::Lifo => self.lots.back_mut(),
+            };
+            let Some(lot) =
+                lot.filter(|lot| lot.quantity.is_sign_positive() != remaining.is_sign_positive())
+            else {
+                break;
+            };
+
+            let closed = remaining.abs().min(lot.quantity.abs());
+            let direction = if lot.quantity.is_sign_positive() {
+                Decimal::ONE
+            } else {
+                Decimal::NEGATIVE_ONE
+            };
+            self.realized += (price - lot.price) * closed * direction;
+            lot.quantity -= closed * direction;
+            remaining += closed * direction;
+
+            if lot.quantity.is_zero() {
+                match method {
+                    CostBasis::Fifo | CostBasis::AverageCost => self.lots.pop_front(),
+                    CostBasis::Lifo => self.lots.pop_back(),
+                };
+            }
+        }
+
+        if remaining.is_zero() {
+            return;
+        }
+        match (method, self.lots.front_mut()) {
+            (CostBasis::AverageCost, Some(lot)) => {
+                let quantity = lot.quantity + remaining;
+                lot.price = (lot.quantity * lot.price + remaining * price) / quantity;
+                lot.quantity = quantity;
+            }
+            _ => self.lots.push_back(Lot {
+                quantity: remaining,
+                price,
+            }),
+        }
+    }
+}
+
+/// The PnL of one symbol, in its quote asset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SymbolPnl {
+    pub symbol: String,
+    pub quantity: Decimal,
+    pub average_cost: Option<Decimal>,
+    pub realized: Decimal,
+    pub unrealized: Option<Decimal>,
+    pub fees: Decimal,
+}
+
+impl SymbolPnl {
+    /// Realized plus unrealized PnL, net of fees. Positions without a mark only count what
+    /// has been realized.
+    pub fn net(&self) -> Decimal {
+        self.realized + self.unrealized.unwrap_or_default() - self.fees
+    }
+}
+
+/// The lot state of a `PnlEngine`, for persisting between incremental runs.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PnlSnapshot {
+    pub method: CostBasis,
+    pub positions: BTreeMap<String, Position>,
+    pub last_fill: Option<DateTime<Utc>>,
+}
+
+/// Computes realized and unrealized PnL per symbol from a sequence of fills.
+///
+/// Fees paid in the quote asset count as is and fees paid in the base asset are valued at the
+/// fill price. Fees in any other asset need a rate set with `set_fee_rate`. Base asset fees
+/// also come out of the position: a bid opens its quantity less the fee and an ask closes its
+/// quantity plus the fee.
+#[derive(Debug, Clone)]
+pub struct PnlEngine {
+    method: CostBasis,
+    positions: BTreeMap<String, Position>,
+    fee_rates: HashMap<(String, String), Decimal>,
+    last_fill: Option<DateTime<Utc>>,
+}
+
+impl PnlEngine {
+    pub fn new(method: CostBasis) -> Self {
+        Self {
+            method,
+            positions: BTreeMap::new(),
+            fee_rates: HashMap::new(),
+            last_fill: None,
+        }
+    }
+
+    pub fn restore(snapshot: PnlSnapshot) -> Self {
+        Self {
+            method: snapshot.method,
+            positions: snapshot.positions,
+            fee_rates: HashMap::new(),
+            last_fill: snapshot.last_fill,
+        }
+    }
+
+    pub fn snapshot(&self) -> PnlSnapshot {
+        PnlSnapshot {
+            method: self.method,
+            positions: self.positions.clone(),
+            last_fill: self.last_fill,
+        }
+    }
+
+    /// Values one unit of `fee_symbol` at `rate` units of `quote` when converting fees.
+    pub fn set_fee_rate(&mut self, fee_symbol: &str, quote: &str, rate: Decimal) {
+        self.fee_rates
+            .insert((fee_symbol.to_string(), quote.to_string()), rate);
+    }
+
+    fn fee_in_quote(&self, fill: &PnlFill) -> Result<Decimal> {
+        let (base, quote) = split_symbol(&fill.symbol);
+        if fill.fee.is_zero() || fill.fee_symbol == quote {
+            return Ok(fill.fee);
+        }
+        if fill.fee_symbol == base {
+            return Ok(fill.fee * fill.price);
+        }
+        self.fee_rates
+            .get(&(fill.fee_symbol.clone(), quote.to_string()))
+            .map(|rate| fill.fee * rate)
+            .ok_or_else(|| {
+                Error::InvalidRequest(format!(
+                    "no rate to convert {} fees into {quote}",
+                    fill.fee_symbol
+                ))
+            })
+    }
+
+    /// Applies `fill`, returning the PnL it realized. Fills must be applied in time order; a
+    /// fill whose fee can't be converted is rejected without changing any state.
+    pub fn apply(&mut self, fill: &PnlFill) -> Result<Decimal> {
+        if let Side::Unknown(side) = &fill.side {
+            return Err(Error::InvalidRequest(format!("unknown fill side {side}")));
+        }
+        let fee = self.fee_in_quote(fill)?;
+        let (base, _) = split_symbol(&fill.symbol);
+        let quantity = match (&fill.side, fill.fee_symbol == base) {
+            (Side::Bid, true) => fill.quantity - fill.fee,
+            (Side::Ask, true) => fill.quantity + fill.fee,
+            _ => fill.quantity,
+        };
+        let position = self.positions.entry(fill.symbol.clone()).or_default();
+        let realized_before = position.realized;
+        position.apply(self.method, &fill.side, fill.price, quantity);
+        position.fees += fee;
+        self.last_fill = self.last_fill.max(Some(fill.timestamp));
+        Ok(position.realized - realized_before)
+    }
+
+    /// The time of the latest fill applied, to resume from after a restore.
+    pub fn last_fill(&self) -> Option<DateTime<Utc>> {
+        self.last_fill
+    }
+
+    pub fn position(&self, symbol: &str) -> Option<&Position> {
+        self.positions.get(symbol)
+    }
+
+    /// The PnL of every symbol seen, valuing open lots at `marks` where a mark is given.
+    pub fn summary(&self, marks: &HashMap<String, Decimal>) -> Vec<SymbolPnl> {
+        self.positions
+            .iter()
+            .map(|(symbol, position)| SymbolPnl {
+                symbol: symbol.clone(),
+                quantity: position.quantity(),
+                average_cost: position.average_cost(),
+                realized: position.realized,
+                unrealized: marks.get(symbol).map(|mark| position.unrealized(*mark)),
+                fees: position.fees,
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SYMBOL: &str = "SOL_USDC";
+
+    fn dec(value: &str) -> Decimal {
+        value.parse().unwrap()
+    }
+
+    fn fill(side: Side, price: &str, quantity: &str, fee: &str, fee_symbol: &str) -> PnlFill {
+        PnlFill {
+            symbol: SYMBOL.to_string(),
+            side,
+            price: dec(price),
+            quantity: dec(quantity),
+            fee: dec(fee),
+            fee_symbol: fee_symbol.to_string(),
+            timestamp: Utc.timestamp_opt(1_700_000_000, 0).unwrap(),
+        }
+    }
+
+    fn buy(price: &str, quantity: &str) -> PnlFill {
+        fill(Side::Bid, price, quantity, "0", "USDC")
+    }
+
+    fn sell(price: &str, quantity: &str) -> PnlFill {
+        fill(Side::Ask, price, quantity, "0", "USDC")
+    }
+
+    fn lots(engine: &PnlEngine) -> Vec<(Decimal, Decimal)> {
+        engine
+            .position(SYMBOL)
+            .unwrap()
+            .lots
+            .iter()
+            .map(|lot| (lot.quantity, lot.price))
+            .collect()
+    }
+
+    fn run(method: CostBasis, fills: &[PnlFill]) -> (PnlEngine, Decimal) {
+        let mut engine = PnlEngine::new(method);
+        let mut realized = Decimal::ZERO;
+        for fill in fills {
+            realized += engine.apply(fill).unwrap();
+        }
+        (engine, realized)
+    }
+
+    #[test]
+    fn fifo_closes_the_oldest_lot() {
+        let fills = [buy("100", "1"), buy("110", "1"), sell("120", "1")];
+        let (engine, realized) = run(CostBasis::Fifo, &fills);
+        assert_eq!(realized, dec("20"));
+        assert_eq!(lots(&engine), vec![(dec("1"), dec("110"))]);
+    }
+
+    #[test]
+    fn lifo_closes_the_newest_lot() {
+        let fills = [buy("100", "1"), buy("110", "1"), sell("120", "1")];
+        let (engine, realized) = run(CostBasis::Lifo, &fills);
+        assert_eq!(realized, dec("10"));
+        assert_eq!(lots(&engine), vec![(dec("1"), dec("100"))]);
+    }
+
+    #[test]
+    fn average_cost_merges_lots() {
+        let fills = [buy("100", "1"), buy("110", "1")];
+        let (mut engine, _) = run(CostBasis::AverageCost, &fills);
+        assert_eq!(lots(&engine), vec![(dec("2"), dec("105"))]);
+
+        assert_eq!(engine.apply(&sell("120", "1")).unwrap(), dec("15"));
+        assert_eq!(lots(&engine), vec![(dec("1"), dec("105"))]);
+    }
+
+    #[test]
+    fn long_flips_to_short() {
+        let fills = [buy("100", "1"), sell("120", "3")];
+        let (engine, realized) = run(CostBasis::Fifo, &fills);
+        assert_eq!(realized, dec("20"));
+        assert_eq!(lots(&engine), vec![(dec("-2"), dec("120"))]);
+
+        let position = engine.position(SYMBOL).unwrap();
+        assert_eq!(position.quantity(), dec("-2"));
+        assert_eq!(position.unrealized(dec("110")), dec("20"));
+
+        let fills = [buy("100", "2"), sell("90", "3")];
+        let (engine, realized) = run(CostBasis::AverageCost, &fills);
+        assert_eq!(realized, dec("-20"));
+        assert_eq!(lots(&engine), vec![(dec("-1"), dec("90"))]);
+        assert_eq!(
+            engine.position(SYMBOL).unwrap().average_cost(),
+            Some(dec("90"))
+        );
+    }
+
+    #[test]
+    fn short_flips_to_long() {
+        let fills = [sell("100", "2"), buy("80", "3")];
+        let (engine, realized) = run(CostBasis::Lifo, &fills);
+        assert_eq!(realized, dec("40"));
+        assert_eq!(lots(&engine), vec![(dec("1"), dec("80"))]);
+    }
+
+    #[test]
+    fn base_asset_fees_come_out_of_the_position() {
+        let fills = [
+            fill(Side::Bid, "100", "1", "0.01", "SOL"),
+            fill(Side::Ask, "120", "0.5", "0.01", "SOL"),
+        ];
+        let (engine, realized) = run(CostBasis::Fifo, &fills);
+        assert_eq!(realized, dec("10.2"));
+
+        let position = engine.position(SYMBOL).unwrap();
+        assert_eq!(position.quantity(), dec("0.48"));
+        assert_eq!(position.fees, dec("2.2"));
+    }
+
+    #[test]
+    fn third_asset_fees_need_a_rate() {
+        let mut engine = PnlEngine::new(CostBasis::Fifo);
+        let fill = fill(Side::Bid, "100", "1", "0.1", "BPX");
+        assert!(engine.apply(&fill).is_err());
+        assert!(engine.position(SYMBOL).is_none());
+        assert_eq!(engine.last_fill(), None);
+
+        engine.set_fee_rate("BPX", "USDC", dec("2"));
+        engine.apply(&fill).unwrap();
+        let position = engine.position(SYMBOL).unwrap();
+        assert_eq!(position.quantity(), dec("1"));
+        assert_eq!(position.fees, dec("0.2"));
+    }
+
+    #[test]
+    fn unknown_sides_are_rejected() {
+        let mut engine = PnlEngine::new(CostBasis::Fifo);
+        let fill = fill(Side::Unknown("Both".to_string()), "100", "1", "0", "USDC");
+        assert!(engine.apply(&fill).is_err());
+        assert!(engine.position(SYMBOL).is_none());
+    }
+
+    #[test]
+    fn snapshot_and_restore_continue_the_same_way() {
+        let fills = [buy("100", "1"), buy("110", "1")];
+        let (mut engine, _) = run(CostBasis::Lifo, &fills);
+
+        let json = serde_json::to_string(&engine.snapshot()).unwrap();
+        let mut restored = PnlEngine::restore(serde_json::from_str(&json).unwrap());
+        assert_eq!(restored.snapshot(), engine.snapshot());
+        assert_eq!(restored.last_fill(), engine.last_fill());
+
+        let close = sell("120", "1");
+        assert_eq!(
+            restored.apply(&close).unwrap(),
+            engine.apply(&close).unwrap()
+        );
+        assert_eq!(lots(&restored), lots(&engine));
+    }
+}
@@ -0,0 +1,188 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use bpx_api_types::{
+    capital::Balance,
+    markets::{Market, Ticker},
+};
+use rust_decimal::Decimal;
+
+use crate::error::Result;
+use crate::BpxClient;
+
+/// A conversion path between two assets and the rate it gives.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route {
+    /// Every asset on the way, starting with the source and ending with the target.
+    pub path: Vec<String>,
+    pub rate: Decimal,
+}
+
+/// Asset conversion rates derived from the last traded price of every market. Each market
+/// converts both ways: base to quote at its price and quote to base at the inverse.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionGraph {
+    edges: HashMap<String, Vec<(String, Decimal)>>,
+}
+
+impl ConversionGraph {
+    pub fn new(markets: &[Market], tickers: &[Ticker]) -> Self {
+        let prices = tickers
+            .iter()
+            .map(|t| (t.symbol.as_str(), t.last_price))
+            .collect::<HashMap<_, _>>();
+
+        let mut graph = Self::default();
+        for market in markets {
+            let Some(&price) = prices.get(market.symbol.as_str()) else {
+                continue;
+            };
+            if price <= Decimal::ZERO {
+                continue;
+            }
+            graph.add_edge(&market.base_symbol, &market.quote_symbol, price);
+            graph.add_edge(
+                &market.quote_symbol,
+                &market.base_symbol,
+                Decimal::ONE / price,
+            );
+        }
+        graph
+    }
+
+    fn add_edge(&mut self, from: &str, to: &str, rate: Decimal) {
+        self.edges
+            .entry(from.to_string())
+            .or_default()
+            .push((to.to_string(), rate));
+    }
+
+    /// The route from `from` to `to` with the fewest hops, if there is one.
+    pub fn route(&self, from: &str, to: &str) -> Option<Route> {
+        if from == to {
+            return Some(Route {
+                path: vec![from.to_string()],
+                rate: Decimal::ONE,
+            });
+        }
+
+        let mut visited = HashSet::from([from]);
+        let mut queue = VecDeque::from([Route {
+            path: vec![from.to_string()],
+            rate: Decimal::ONE,
+        }]);
+        while let Some(route) = queue.pop_front() {
+            let last = route.path.last().expect("routes are never empty");
+            for (next, rate) in self.edges.get(last).into_iter().flatten() {
+                if !visited.insert(next) {
+                    continue;
+                }
+                let mut path = route.path.clone();
+                path.push(next.clone());
+                let extended = Route {
+                    path,
+                    rate: route.rate * rate,
+                };
+                if next == to {
+                    return Some(extended);
+                }
+                queue.push_back(extended);
+            }
+        }
+        None
+    }
+}
+
+/// One asset of a `Valuation`.
+#[derive(Debug, Clone)]
+pub struct AssetValue {
+    pub symbol: String,
+    pub balance: Balance,
+    pub route: Route,
+    pub available: Decimal,
+    pub locked: Decimal,
+    pub staked: Decimal,
+    pub total: Decimal,
+}
+
+/// Account balances valued in a single quote asset.
+#[derive(Debug, Clone)]
+pub struct Valuation {
+    pub quote: String,
+    pub assets: Vec<AssetValue>,
+    pub available: Decimal,
+    pub locked: Decimal,
+    pub staked: Decimal,
+    pub total: Decimal,
+    /// Assets with a non-zero balance but no route to the quote asset. They are left out of
+    /// the totals rather than counted as worthless.
+    pub unpriced: Vec<String>,
+}
+
+impl Valuation {
+    pub fn from_balances(
+        balances: &HashMap<String, Balance>,
+        graph: &ConversionGraph,
+        quote: &str,
+    ) -> Self {
+        let mut valuation = Valuation {
+            quote: quote.to_string(),
+            assets: Vec::new(),
+            available: Decimal::ZERO,
+            locked: Decimal::ZERO,
+            staked: Decimal::ZERO,
+            total: Decimal::ZERO,
+            unpriced: Vec::new(),
+        };
+
+        let mut symbols = balances.keys().collect::<Vec<_>>();
+        symbols.sort();
+        for symbol in symbols {
+            let balance = &balances[symbol];
+            let quantity = balance.available + balance.locked + balance.staked;
+            let Some(route) = graph.route(symbol, quote) else {
+                if !quantity.is_zero() {
+                    valuation.unpriced.push(symbol.clone());
+                }
+                continue;
+            };
+
+            let asset = AssetValue {
+                symbol: symbol.clone(),
+                balance: balance.clone(),
+                available: balance.available * route.rate,
+                locked: balance.locked * route.rate,
+                staked: balance.staked * route.rate,
+                total: quantity * route.rate,
+                route,
+            };
+            valuation.available += asset.available;
+            valuation.locked += asset.locked;
+            valuation.staked += asset.staked;
+            valuation.total += asset.total;
+            valuation.assets.push(asset);
+        }
+        valuation
+    }
+}
+
+/// Values the account's balances through the client's markets and tickers.
+#[derive(Debug, Clone, Copy)]
+pub struct Portfolio<'a> {
+    client: &'a BpxClient,
+}
+
+impl<'a> Portfolio<'a> {
+    pub fn new(client: &'a BpxClient) -> Self {
+        Self { client }
+    }
+
+    /// Values every balance in `quote`, converting through as many markets as needed, e.g.
+    /// X to SOL to USDC.
+    pub async fn value_in(&self, quote: &str) -> Result<Valuation> {
+        let balances = self.client.get_balances().await?;
+        let markets = self.client.get_markets().await?;
+        let tickers = self.client.get_tickers().await?;
+        let graph = ConversionGraph::new(&markets, &tickers);
+        Ok(Valuation::from_balances(&balances, &graph, quote))
+    }
+}
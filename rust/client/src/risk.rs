@@ -0,0 +1,309 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+use bpx_api_types::capital::Balance;
+use bpx_api_types::order::{ExecuteOrderPayload, Side};
+use rust_decimal::Decimal;
+use strum::Display;
+use tokio::time::Instant;
+
+use crate::error::{Error, Result};
+use crate::BpxClient;
+
+/// The risk limit an order breached.
+#[derive(Debug, Display, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RiskRule {
+    MaxNotional,
+    MaxOpenOrders,
+    MaxPosition,
+    PriceBand,
+    OrderRate,
+}
+
+/// The price the price band is measured from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PriceReference {
+    /// The last traded price from the symbol's ticker.
+    LastPrice,
+    /// The midpoint of the best bid and ask.
+    BookMid,
+}
+
+/// Pre-trade checks run on every `execute_order` and `execute_orders` before it is signed. An
+/// order that breaches a limit is rejected with `Error::RiskRejected` and never sent; a batch
+/// is rejected as a whole.
+///
+/// Checks that need market or account data fetch it once per order or batch, so only
+/// configure the ones you need.
+#[derive(Debug, Default)]
+pub struct RiskLimits {
+    max_notional: HashMap<String, Decimal>,
+    max_position: HashMap<String, Decimal>,
+    max_open_orders: Option<usize>,
+    price_band: Option<(Decimal, PriceReference)>,
+    max_orders_per_second: Option<usize>,
+    sent: Mutex<VecDeque<Instant>>,
+}
+
+impl RiskLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Caps the value of a single order on `symbol`, in its quote asset.
+    pub fn max_notional(mut self, symbol: impl Into<String>, notional: Decimal) -> Self {
+        self.max_notional.insert(symbol.into(), notional);
+        self
+    }
+
+    /// Caps the base asset of `symbol` held once an order fills, long or short.
+    pub fn max_position(mut self, symbol: impl Into<String>, quantity: Decimal) -> Self {
+        self.max_position.insert(symbol.into(), quantity);
+        self
+    }
+
+    /// Caps the number of open orders across all symbols.
+    pub fn max_open_orders(mut self, count: usize) -> Self {
+        self.max_open_orders = Some(count);
+        self
+    }
+
+    /// Rejects priced orders more than `band` (e.g. 0.05 for 5%) through `reference`: bids
+    /// above it and asks below it.
+    pub fn price_band(mut self, band: Decimal, reference: PriceReference) -> Self {
+        self.price_band = Some((band, reference));
+        self
+    }
+
+    pub fn max_orders_per_second(mut self, count: usize) -> Self {
+        self.max_orders_per_second = Some(count);
+        self
+    }
+
+    fn sent(&self) -> MutexGuard<'_, VecDeque<Instant>> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// Counts `count` orders against the per-second limit if there is room for all of them.
+    fn take_order_slots(&self, count: usize) -> Result<()> {
+        let Some(limit) = self.max_orders_per_second else {
+            return Ok(());
+        };
+        let now = Instant::now();
+        let mut sent = self.sent();
+        while sent
+            .front()
+            .is_some_and(|t| now.duration_since(*t) >= Duration::from_secs(1))
+        {
+            sent.pop_front();
+        }
+        if sent.len() + count > limit {
+            return Err(rejected(
+                RiskRule::OrderRate,
+                format!("more than {limit} orders in the last second"),
+            ));
+        }
+        sent.extend(std::iter::repeat_n(now, count));
+        Ok(())
+    }
+}
+
+fn rejected(rule: RiskRule, detail: String) -> Error {
+    Error::RiskRejected { rule, detail }
+}
+
+/// Market and account data fetched while checking one batch, so that each is fetched once.
+struct RiskData<'a> {
+    client: &'a BpxClient,
+    prices: HashMap<(String, PriceReference), Decimal>,
+    balances: Option<HashMap<String, Balance>>,
+    /// The base asset held per symbol once the batch's orders checked so far have filled.
+    positions: HashMap<String, Decimal>,
+}
+
+impl<'a> RiskData<'a> {
+    fn new(client: &'a BpxClient) -> Self {
+        Self {
+            client,
+            prices: HashMap::new(),
+            balances: None,
+            positions: HashMap::new(),
+        }
+    }
+
+    async fn price(
+        &mut self,
+        symbol: &str,
+        reference: PriceReference,
+        rule: RiskRule,
+    ) -> Result<Decimal> {
+        let key = (symbol.to_string(), reference);
+        if let Some(&price) = self.prices.get(&key) {
+            return Ok(price);
+        }
+        let price = self.client.reference_price(symbol, reference, rule).await?;
+        self.prices.insert(key, price);
+        Ok(price)
+    }
+
+    /// The account's total holding of the base asset of `symbol`, plus what the batch's
+    /// earlier orders on it add.
+    async fn position(&mut self, symbol: &str) -> Result<Decimal> {
+        if let Some(&position) = self.positions.get(symbol) {
+            return Ok(position);
+        }
+        let market = self
+            .client
+            .get_market_cached(symbol)
+            .await?
+            .ok_or_else(|| Error::InvalidRequest(format!("unknown market {symbol}")))?;
+        let balances = match &mut self.balances {
+            Some(balances) => balances,
+            balances => balances.insert(self.client.get_balances().await?),
+        };
+        let held = balances
+            .get(&market.base_symbol)
+            .map(|b| b.available + b.locked + b.staked)
+            .unwrap_or_default();
+        self.positions.insert(symbol.to_string(), held);
+        Ok(held)
+    }
+}
+
+impl BpxClient {
+    /// Runs `payload` through the client's risk limits without sending it.
+    pub async fn check_risk(&self, payload: &ExecuteOrderPayload) -> Result<()> {
+        self.check_risk_batch(std::slice::from_ref(payload)).await
+    }
+
+    /// Runs `payloads` through the client's risk limits as one batch without sending them.
+    /// Position, open-order and rate limits count the whole batch, and the batch takes its
+    /// rate-limit slots only if all of it passes.
+    pub async fn check_risk_batch(&self, payloads: &[ExecuteOrderPayload]) -> Result<()> {
+        let Some(limits) = &self.risk_limits else {
+            return Ok(());
+        };
+        let mut data = RiskData::new(self);
+
+        for payload in payloads {
+            let symbol = &payload.symbol;
+
+            let mut reference = None;
+            if let Some((band, kind)) = limits.price_band {
+                let mark = data.price(symbol, kind, RiskRule::PriceBand).await?;
+                reference = Some(mark);
+                if let Some(price) = payload.price {
+                    let (breached, limit) = match &payload.side {
+                        Side::Bid => (price > mark * (Decimal::ONE + band), "above"),
+                        Side::Ask => (price < mark * (Decimal::ONE - band), "below"),
+                        Side::Unknown(_) => (false, ""),
+                    };
+                    if breached {
+                        return Err(rejected(
+                            RiskRule::PriceBand,
+                            format!(
+                                "{} {price} is more than {band} {limit} {mark}",
+                                payload.side
+                            ),
+                        ));
+                    }
+                }
+            }
+
+            let max_notional = limits.max_notional.get(symbol);
+            let max_position = limits.max_position.get(symbol);
+            if max_notional.is_none() && max_position.is_none() {
+                continue;
+            }
+            let price = match payload.price.or(reference) {
+                Some(price) => price,
+                None => {
+                    let rule = if max_notional.is_some() {
+                        RiskRule::MaxNotional
+                    } else {
+                        RiskRule::MaxPosition
+                    };
+                    data.price(symbol, PriceReference::LastPrice, rule).await?
+                }
+            };
+            let (quantity, notional) = match (payload.quantity, payload.quote_quantity) {
+                (Some(quantity), _) => (quantity, quantity * price),
+                (None, Some(quote_quantity)) if !price.is_zero() => {
+                    (quote_quantity / price, quote_quantity)
+                }
+                _ => (Decimal::ZERO, Decimal::ZERO),
+            };
+
+            if let Some(&max) = max_notional {
+                if notional > max {
+                    return Err(rejected(
+                        RiskRule::MaxNotional,
+                        format!("notional {notional} exceeds {max} on {symbol}"),
+                    ));
+                }
+            }
+
+            if let Some(&max) = max_position {
+                let held = data.position(symbol).await?;
+                let after = match &payload.side {
+                    Side::Bid => held + quantity,
+                    Side::Ask => held - quantity,
+                    Side::Unknown(_) => held,
+                };
+                if after.abs() > max {
+                    return Err(rejected(
+                        RiskRule::MaxPosition,
+                        format!("position would be {after}, over {max} on {symbol}"),
+                    ));
+                }
+                data.positions.insert(symbol.clone(), after);
+            }
+        }
+
+        if let Some(max) = limits.max_open_orders {
+            let open = self.get_open_orders(None).await?.len();
+            if open + payloads.len() > max {
+                return Err(rejected(
+                    RiskRule::MaxOpenOrders,
+                    format!(
+                        "{open} orders already open, {} more would exceed the limit of {max}",
+                        payloads.len()
+                    ),
+                ));
+            }
+        }
+
+        limits.take_order_slots(payloads.len())
+    }
+
+    /// The reference price of `symbol`; a missing price rejects the order under `rule`, the
+    /// rule that needed it.
+    async fn reference_price(
+        &self,
+        symbol: &str,
+        reference: PriceReference,
+        rule: RiskRule,
+    ) -> Result<Decimal> {
+        let price = match reference {
+            PriceReference::LastPrice => {
+                let ticker = self.get_ticker(symbol).await?;
+                Some(ticker.last_price).filter(|price| *price > Decimal::ZERO)
+            }
+            PriceReference::BookMid => {
+                let depth = self.get_order_book_depth(symbol).await?;
+                let best_bid = depth.bids.iter().map(|(price, _)| *price).max();
+                let best_ask = depth.asks.iter().map(|(price, _)| *price).min();
+                best_bid
+                    .zip(best_ask)
+                    .map(|(bid, ask)| (bid + ask) / Decimal::TWO)
+            }
+        };
+        price.ok_or_else(|| {
+            rejected(
+                rule,
+                format!("no {reference:?} reference price for {symbol}"),
+            )
+        })
+    }
+}
@@ -0,0 +1,174 @@
+use std::io::Write;
+
+use bpx_api_types::{
+    capital::{Deposit, Withdrawal},
+    history::{Fill, HistoryQuery},
+    order::Side,
+};
+use chrono::{DateTime, Utc};
+use futures::{Stream, TryStreamExt};
+use rust_decimal::Decimal;
+use serde::Serialize;
+
+use crate::error::Result;
+use crate::pagination::{PageOptions, Paged};
+use crate::BpxClient;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum StatementFormat {
+    Csv,
+    JsonLines,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum EntryKind {
+    Deposit,
+    Withdrawal,
+    Trade,
+}
+
+/// One line of an account statement. The field order is the column order of the CSV export
+/// and must not change.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+    pub timestamp: DateTime<Utc>,
+    pub kind: EntryKind,
+    pub asset: String,
+    /// Positive when the asset came into the account.
+    pub amount: Decimal,
+    pub fee: Decimal,
+    pub fee_asset: Option<String>,
+    pub counter_asset: Option<String>,
+    pub counter_amount: Option<Decimal>,
+    pub tx_hash: Option<String>,
+    pub identifier: Option<String>,
+    pub reference: String,
+    pub status: String,
+}
+
+impl From<&Deposit> for LedgerEntry {
+    fn from(deposit: &Deposit) -> Self {
+        Self {
+            timestamp: deposit.created_at.and_utc(),
+            kind: EntryKind::Deposit,
+            asset: deposit.symbol.clone(),
+            amount: deposit.quantity,
+            fee: Decimal::ZERO,
+            fee_asset: None,
+            counter_asset: None,
+            counter_amount: None,
+            // Deposits identify their on-chain transaction through `identifier`.
+            tx_hash: deposit.identifier.clone(),
+            identifier: deposit.identifier.clone(),
+            reference: deposit.id.to_string(),
+            status: deposit.status.to_string(),
+        }
+    }
+}
+
+impl From<&Withdrawal> for LedgerEntry {
+    fn from(withdrawal: &Withdrawal) -> Self {
+        Self {
+            timestamp: withdrawal.created_at.and_utc(),
+            kind: EntryKind::Withdrawal,
+            asset: withdrawal.symbol.clone(),
+            amount: -withdrawal.quantity,
+            fee: withdrawal.fee,
+            fee_asset: Some(withdrawal.symbol.clone()),
+            counter_asset: None,
+            counter_amount: None,
+            tx_hash: withdrawal.transaction_hash.clone(),
+            identifier: withdrawal.identifier.clone(),
+            reference: withdrawal.id.to_string(),
+            status: withdrawal.status.to_string(),
+        }
+    }
+}
+
+impl From<&Fill> for LedgerEntry {
+    fn from(fill: &Fill) -> Self {
+        let (base, quote) = fill.symbol.split_once('_').unwrap_or((&fill.symbol, ""));
+        let notional = fill.price * fill.quantity;
+        let (amount, counter_amount) = match fill.side {
+            Side::Ask => (-fill.quantity, notional),
+            _ => (fill.quantity, -notional),
+        };
+        Self {
+            timestamp: fill.timestamp.and_utc(),
+            kind: EntryKind::Trade,
+            asset: base.to_string(),
+            amount,
+            fee: fill.fee,
+            fee_asset: Some(fill.fee_symbol.clone()),
+            counter_asset: Some(quote.to_string()),
+            counter_amount: Some(counter_amount),
+            tx_hash: None,
+            identifier: fill.trade_id.map(|id| id.to_string()),
+            reference: fill.order_id.clone(),
+            status: "filled".to_string(),
+        }
+    }
+}
+
+impl BpxClient {
+    /// Writes every deposit, withdrawal and fill between `from` and `to` to `writer`, oldest
+    /// first, and returns the number of entries written.
+    pub async fn export_statement<W: Write>(
+        &self,
+        from: DateTime<Utc>,
+        to: DateTime<Utc>,
+        format: StatementFormat,
+        writer: W,
+    ) -> Result<usize> {
+        let options = PageOptions::default().since(from).until(to);
+        let query = HistoryQuery {
+            from: Some(from),
+            to: Some(to),
+            ..Default::default()
+        };
+
+        let mut entries = collect_entries(self.deposits_stream(options.clone())).await?;
+        entries.extend(collect_entries(self.withdrawals_stream(options.clone())).await?);
+        entries.extend(collect_entries(self.fill_history_stream(&query, options)).await?);
+        entries.sort_by_key(|entry| entry.timestamp);
+
+        write_entries(&entries, format, writer)?;
+        Ok(entries.len())
+    }
+}
+
+async fn collect_entries<T>(items: impl Stream<Item = Result<Paged<T>>>) -> Result<Vec<LedgerEntry>>
+where
+    for<'a> &'a T: Into<LedgerEntry>,
+{
+    items
+        .map_ok(|paged| (&paged.item).into())
+        .try_collect()
+        .await
+}
+
+pub fn write_entries<W: Write>(
+    entries: &[LedgerEntry],
+    format: StatementFormat,
+    mut writer: W,
+) -> Result<()> {
+    match format {
+        StatementFormat::Csv => {
+            let mut csv = csv::Writer::from_writer(writer);
+            for entry in entries {
+                csv.serialize(entry)?;
+            }
+            csv.flush()?;
+        }
+        StatementFormat::JsonLines => {
+            for entry in entries {
+                serde_json::to_writer(&mut writer, entry)?;
+                writer.write_all(b"\n")?;
+            }
+            writer.flush()?;
+        }
+    }
+    Ok(())
+}
@@ -1,6 +1,8 @@
 use bpx_api_types::trade::Trade;
+use futures::Stream;
 
 use crate::error::Result;
+use crate::pagination::{paginate, PageOptions, Paged};
 use crate::BpxClient;
 
 impl BpxClient {
@@ -28,4 +30,16 @@ impl BpxClient {
         let res = self.get(url).await?;
         res.json().await.map_err(Into::into)
     }
+
+    /// Streams the trade history of `symbol`, newest first, paging through
+    /// `get_historical_trades`.
+    pub fn historical_trades_stream<'a>(
+        &'a self,
+        symbol: &'a str,
+        options: PageOptions,
+    ) -> impl Stream<Item = Result<Paged<Trade>>> + 'a {
+        paginate(options, move |limit, offset| {
+            self.get_historical_trades(symbol, Some(limit), Some(offset))
+        })
+    }
 }
@@ -0,0 +1,159 @@
+use bpx_api_types::{
+    markets::Market,
+    order::{ExecuteOrderPayload, OrderType, TimeInForce},
+};
+use rust_decimal::Decimal;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum Violation {
+    #[error("unknown market {0}")]
+    UnknownMarket(String),
+
+    #[error("limit orders require a price")]
+    MissingPrice,
+
+    #[error("price {price} is outside [{min}, {}]", display_bound(.max))]
+    PriceOutOfBounds {
+        price: Decimal,
+        min: Decimal,
+        max: Option<Decimal>,
+    },
+
+    #[error("price {price} is not a multiple of tick size {tick_size}")]
+    PriceOffTick { price: Decimal, tick_size: Decimal },
+
+    #[error("trigger price {price} is not a multiple of tick size {tick_size}")]
+    TriggerPriceOffTick { price: Decimal, tick_size: Decimal },
+
+    #[error("either quantity or quote_quantity is required")]
+    MissingQuantity,
+
+    #[error("market orders take quantity or quote_quantity, not both")]
+    ConflictingQuantities,
+
+    #[error("quantity {quantity} is outside [{min}, {}]", display_bound(.max))]
+    QuantityOutOfBounds {
+        quantity: Decimal,
+        min: Decimal,
+        max: Option<Decimal>,
+    },
+
+    #[error("quantity {quantity} is not a multiple of step size {step_size}")]
+    QuantityOffStep {
+        quantity: Decimal,
+        step_size: Decimal,
+    },
+
+    #[error("post_only cannot be combined with {0} time in force")]
+    PostOnlyWithTimeInForce(TimeInForce),
+}
+
+fn display_bound(bound: &Option<Decimal>) -> String {
+    bound.map_or_else(|| "unbounded".to_string(), |b| b.to_string())
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    pub symbol: String,
+    pub violations: Vec<Violation>,
+}
+
+impl std::fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "order for {} failed validation: ", self.symbol)?;
+        for (i, violation) in self.violations.iter().enumerate() {
+            if i > 0 {
+                f.write_str("; ")?;
+            }
+            write!(f, "{violation}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ValidationError {}
+
+/// Checks `payload` against the filters of `market`, collecting every violation rather than
+/// stopping at the first one.
+pub fn validate_order(
+    payload: &ExecuteOrderPayload,
+    market: &Market,
+) -> std::result::Result<(), ValidationError> {
+    let price_filters = &market.filters.price;
+    let quantity_filters = &market.filters.quantity;
+    let mut violations = Vec::new();
+
+    match payload.order_type {
+        OrderType::Limit => {
+            if payload.price.is_none() {
+                violations.push(Violation::MissingPrice);
+            }
+            if payload.quantity.is_none() {
+                violations.push(Violation::MissingQuantity);
+            }
+        }
+        OrderType::Market => match (payload.quantity, payload.quote_quantity) {
+            (None, None) => violations.push(Violation::MissingQuantity),
+            (Some(_), Some(_)) => violations.push(Violation::ConflictingQuantities),
+            _ => {}
+        },
+        // The exchange knows the rules for order types this crate doesn't model.
+        OrderType::Unknown(_) => {}
+    }
+
+    if let Some(price) = payload.price {
+        if !price_filters.in_bounds(price) {
+            violations.push(Violation::PriceOutOfBounds {
+                price,
+                min: price_filters.min_price,
+                max: price_filters.max_price,
+            });
+        }
+        if !price_filters.is_on_tick(price) {
+            violations.push(Violation::PriceOffTick {
+                price,
+                tick_size: price_filters.tick_size,
+            });
+        }
+    }
+
+    if let Some(price) = payload.trigger_price {
+        if !price_filters.is_on_tick(price) {
+            violations.push(Violation::TriggerPriceOffTick {
+                price,
+                tick_size: price_filters.tick_size,
+            });
+        }
+    }
+
+    if let Some(quantity) = payload.quantity {
+        if !quantity_filters.in_bounds(quantity) {
+            violations.push(Violation::QuantityOutOfBounds {
+                quantity,
+                min: quantity_filters.min_quantity,
+                max: quantity_filters.max_quantity,
+            });
+        }
+        if !quantity_filters.is_on_step(quantity) {
+            violations.push(Violation::QuantityOffStep {
+                quantity,
+                step_size: quantity_filters.step_size,
+            });
+        }
+    }
+
+    if payload.post_only == Some(true) {
+        if let Some(tif @ (TimeInForce::IOC | TimeInForce::FOK)) = &payload.time_in_force {
+            violations.push(Violation::PostOnlyWithTimeInForce(tif.clone()));
+        }
+    }
+
+    if violations.is_empty() {
+        Ok(())
+    } else {
+        Err(ValidationError {
+            symbol: payload.symbol.clone(),
+            violations,
+        })
+    }
+}
@@ -0,0 +1,267 @@
+use bpx_api_types::{capital::RequestWithdrawalPayload, markets::Token, Blockchain};
+use rust_decimal::{prelude::ToPrimitive, Decimal, RoundingStrategy};
+
+use crate::error::{Error, Result};
+use crate::BpxClient;
+
+/// The fewest decimal places a split leg is rounded to.
+const MIN_SPLIT_SCALE: u32 = 8;
+
+/// One withdrawal of a `WithdrawalPlan`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawalLeg {
+    pub blockchain: Blockchain,
+    pub quantity: Decimal,
+    pub fee: Decimal,
+    /// What arrives at the destination: the quantity less the fee.
+    pub net: Decimal,
+}
+
+/// The cheapest way found to withdraw an amount of one asset.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WithdrawalPlan {
+    pub symbol: String,
+    pub legs: Vec<WithdrawalLeg>,
+}
+
+impl WithdrawalPlan {
+    /// Plans withdrawing `amount` of `symbol` over one of its `tokens`, one per blockchain.
+    /// Only networks with withdrawals enabled and, unless `allowed_chains` is empty, listed in
+    /// it are considered. Amounts over a network's maximum are split evenly into as few
+    /// withdrawals as fit, and the network with the lowest total fee wins.
+    pub fn new(
+        symbol: &str,
+        amount: Decimal,
+        tokens: &[Token],
+        allowed_chains: &[Blockchain],
+    ) -> Result<Self> {
+        if amount <= Decimal::ZERO {
+            return Err(Error::InvalidRequest(format!(
+                "cannot withdraw {amount} {symbol}"
+            )));
+        }
+
+        let candidates = tokens
+            .iter()
+            .filter(|t| t.withdrawal_enabled)
+            .filter(|t| allowed_chains.is_empty() || allowed_chains.contains(&t.blockchain))
+            .collect::<Vec<_>>();
+        if candidates.is_empty() {
+            return Err(Error::InvalidRequest(format!(
+                "no allowed network has {symbol} withdrawals enabled"
+            )));
+        }
+
+        let mut rejections = Vec::new();
+        let mut best: Option<Vec<WithdrawalLeg>> = None;
+        for token in candidates {
+            match split(amount, token) {
+                Ok(legs) => {
+                    let fee = total_fee(&legs);
+                    if best.as_ref().is_none_or(|best| fee < total_fee(best)) {
+                        best = Some(legs);
+                    }
+                }
+                Err(reason) => rejections.push(format!("{}: {reason}", token.blockchain)),
+            }
+        }
+
+        best.map(|legs| Self {
+            symbol: symbol.to_string(),
+            legs,
+        })
+        .ok_or_else(|| {
+            Error::InvalidRequest(format!(
+                "cannot withdraw {amount} {symbol}: {}",
+                rejections.join(", ")
+            ))
+        })
+    }
+
+    pub fn blockchain(&self) -> &Blockchain {
+        &self.legs[0].blockchain
+    }
+
+    pub fn quantity(&self) -> Decimal {
+        self.legs.iter().map(|leg| leg.quantity).sum()
+    }
+
+    pub fn fee(&self) -> Decimal {
+        total_fee(&self.legs)
+    }
+
+    pub fn net(&self) -> Decimal {
+        self.legs.iter().map(|leg| leg.net).sum()
+    }
+
+    /// A payload for each leg, withdrawing to `address`.
+    pub fn payloads(&self, address: &str) -> Vec<RequestWithdrawalPayload> {
+        self.legs
+            .iter()
+            .map(|leg| RequestWithdrawalPayload {
+                address: address.to_string(),
+                blockchain: leg.blockchain.clone(),
+                quantity: leg.quantity,
+                symbol: self.symbol.clone(),
+                ..Default::default()
+            })
+            .collect()
+    }
+}
+
+fn total_fee(legs: &[WithdrawalLeg]) -> Decimal {
+    legs.iter().map(|leg| leg.fee).sum()
+}
+
+/// Splits `amount` into equal withdrawals no larger than the token's maximum. Legs are rounded
+/// down to the finest precision among the amount and the token's limits, and at least
+/// `MIN_SPLIT_SCALE` places; the last leg absorbs the rounding.
+fn split(amount: Decimal, token: &Token) -> std::result::Result<Vec<WithdrawalLeg>, String> {
+    if amount < token.minimum_withdrawal {
+        return Err(format!("below the minimum of {}", token.minimum_withdrawal));
+    }
+
+    let mut count = match token.maximum_withdrawal {
+        Some(maximum) if maximum <= Decimal::ZERO => {
+            return Err(format!("the maximum withdrawal is {maximum}"))
+        }
+        Some(maximum) => (amount / maximum)
+            .ceil()
+            .to_usize()
+            .unwrap_or(usize::MAX)
+            .max(1),
+        None => 1,
+    };
+    let scale = [
+        amount,
+        token.minimum_withdrawal,
+        token.maximum_withdrawal.unwrap_or_default(),
+        token.withdrawal_fee,
+    ]
+    .iter()
+    .map(Decimal::scale)
+    .fold(MIN_SPLIT_SCALE, u32::max);
+    loop {
+        let quantity =
+            (amount / Decimal::from(count)).round_dp_with_strategy(scale, RoundingStrategy::ToZero);
+        // Legs only shrink as the count grows, so there is no point retrying past here.
+        if quantity.is_zero() || quantity < token.minimum_withdrawal {
+            return Err(format!(
+                "splitting under the maximum of {} leaves withdrawals below the minimum of {}",
+                token.maximum_withdrawal.unwrap_or_default(),
+                token.minimum_withdrawal
+            ));
+        }
+        let last = amount - quantity * Decimal::from(count - 1);
+        if token
+            .maximum_withdrawal
+            .is_some_and(|maximum| last > maximum)
+        {
+            count += 1;
+            continue;
+        }
+        if quantity <= token.withdrawal_fee {
+            return Err(format!(
+                "the fee of {} takes everything",
+                token.withdrawal_fee
+            ));
+        }
+
+        let leg = |quantity| WithdrawalLeg {
+            blockchain: token.blockchain.clone(),
+            quantity,
+            fee: token.withdrawal_fee,
+            net: quantity - token.withdrawal_fee,
+        };
+        let mut legs = vec![leg(quantity); count - 1];
+        legs.push(leg(last));
+        return Ok(legs);
+    }
+}
+
+impl BpxClient {
+    /// Plans the cheapest withdrawal of `amount` of `symbol` using the networks listed by
+    /// `get_assets`. See `WithdrawalPlan::new`.
+    pub async fn plan_withdrawal(
+        &self,
+        symbol: &str,
+        amount: Decimal,
+        allowed_chains: &[Blockchain],
+    ) -> Result<WithdrawalPlan> {
+        let assets = self.get_assets().await?;
+        let tokens = assets
+            .get(symbol)
+            .ok_or_else(|| Error::InvalidRequest(format!("unknown asset {symbol}")))?;
+        WithdrawalPlan::new(symbol, amount, tokens, allowed_chains)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(minimum: &str, maximum: Option<&str>, fee: &str) -> Token {
+        Token {
+            blockchain: Blockchain::Bitcoin,
+            deposit_enabled: true,
+            minimum_deposit: Decimal::ZERO,
+            withdrawal_enabled: true,
+            minimum_withdrawal: minimum.parse().unwrap(),
+            maximum_withdrawal: maximum.map(|maximum| maximum.parse().unwrap()),
+            withdrawal_fee: fee.parse().unwrap(),
+        }
+    }
+
+    fn quantities(legs: &[WithdrawalLeg]) -> Vec<Decimal> {
+        legs.iter().map(|leg| leg.quantity).collect()
+    }
+
+    #[test]
+    fn whole_amounts_split_like_fractional_ones() {
+        let token = token("0.001", Some("0.75"), "0.0001");
+        let whole = split(Decimal::from(2), &token).unwrap();
+        let fractional = split("2.0".parse().unwrap(), &token).unwrap();
+        assert_eq!(whole.len(), 3);
+        assert_eq!(quantities(&whole), quantities(&fractional));
+        assert_eq!(quantities(&whole).iter().sum::<Decimal>(), Decimal::from(2));
+        assert!(whole
+            .iter()
+            .all(|leg| leg.quantity <= "0.75".parse().unwrap()));
+    }
+
+    #[test]
+    fn exact_multiples_of_the_maximum_split_evenly() {
+        let token = token("0.001", Some("0.5"), "0.0001");
+        let legs = split(Decimal::from(2), &token).unwrap();
+        assert_eq!(
+            quantities(&legs),
+            vec!["0.5".parse::<Decimal>().unwrap(); 4]
+        );
+        assert!(legs.iter().all(|leg| leg.net == "0.4999".parse().unwrap()));
+    }
+
+    #[test]
+    fn amounts_under_the_maximum_are_not_split() {
+        let token = token("0.001", None, "0.0001");
+        let legs = split(Decimal::from(2), &token).unwrap();
+        assert_eq!(quantities(&legs), vec![Decimal::from(2)]);
+    }
+
+    #[test]
+    fn amounts_below_the_minimum_are_rejected() {
+        let token = token("0.01", Some("0.75"), "0.0001");
+        assert!(split("0.005".parse().unwrap(), &token).is_err());
+    }
+
+    #[test]
+    fn splits_below_the_minimum_are_rejected() {
+        let token = token("0.5", Some("0.6"), "0.0001");
+        assert!(split("1.3".parse().unwrap(), &token).is_err());
+    }
+
+    #[test]
+    fn fees_that_take_everything_are_rejected() {
+        let token = token("0.001", Some("0.75"), "1");
+        assert!(split(Decimal::from(2), &token).is_err());
+    }
+}
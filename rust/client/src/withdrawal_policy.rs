@@ -0,0 +1,217 @@
+use std::collections::{BTreeMap, HashMap};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use bpx_api_types::{capital::RequestWithdrawalPayload, Blockchain};
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::error::Result;
+use crate::write_atomically;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum PolicyViolation {
+    #[error("{address} on {blockchain} is not on the withdrawal allowlist")]
+    AddressNotAllowed {
+        blockchain: Blockchain,
+        address: String,
+    },
+
+    #[error("{address} on {blockchain} can't be withdrawn to before {usable_at}")]
+    AddressTooNew {
+        blockchain: Blockchain,
+        address: String,
+        usable_at: DateTime<Utc>,
+    },
+
+    #[error(
+        "withdrawing {quantity} {symbol} exceeds the daily limit of {limit} ({used} used today)"
+    )]
+    DailyLimitExceeded {
+        symbol: String,
+        quantity: Decimal,
+        used: Decimal,
+        limit: Decimal,
+    },
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AllowedAddress {
+    pub blockchain: Blockchain,
+    pub address: String,
+    /// When the address was added; it can't be used until the policy's delay has passed.
+    pub added_at: DateTime<Utc>,
+}
+
+/// Quantities withdrawn per UTC day and asset.
+type Ledger = BTreeMap<NaiveDate, HashMap<String, Decimal>>;
+
+/// Checks run on every `request_withdrawal` before it is signed: the destination must be on
+/// the allowlist and old enough, and per-asset daily limits must hold. Withdrawn quantities
+/// are tracked in a ledger that is optionally persisted to a file so limits survive restarts.
+///
+/// In dry-run mode the checks still run but nothing is sent or recorded, and
+/// `request_withdrawal` returns `Error::WithdrawalDryRun` once they pass.
+#[derive(Debug, Default)]
+pub struct WithdrawalPolicy {
+    allowlist: Vec<AllowedAddress>,
+    daily_limits: HashMap<String, Decimal>,
+    new_address_delay: Duration,
+    dry_run: bool,
+    ledger_path: Option<PathBuf>,
+    ledger: Mutex<Ledger>,
+}
+
+impl WithdrawalPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn allow(
+        mut self,
+        blockchain: Blockchain,
+        address: impl Into<String>,
+        added_at: DateTime<Utc>,
+    ) -> Self {
+        self.allowlist.push(AllowedAddress {
+            blockchain,
+            address: address.into(),
+            added_at,
+        });
+        self
+    }
+
+    /// Caps the quantity of `symbol` withdrawn per UTC day. Assets without a limit are
+    /// unlimited.
+    pub fn daily_limit(mut self, symbol: impl Into<String>, limit: Decimal) -> Self {
+        self.daily_limits.insert(symbol.into(), limit);
+        self
+    }
+
+    /// How long a newly allowed address has to wait before it can be withdrawn to.
+    pub fn new_address_delay(mut self, delay: Duration) -> Self {
+        self.new_address_delay = delay;
+        self
+    }
+
+    pub fn dry_run(mut self, dry_run: bool) -> Self {
+        self.dry_run = dry_run;
+        self
+    }
+
+    /// Keeps the ledger of withdrawn quantities in `path`, loading it if it already exists.
+    pub fn ledger_file(mut self, path: impl AsRef<Path>) -> Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        if path.exists() {
+            let contents = std::fs::read_to_string(&path)?;
+            self.ledger = Mutex::new(serde_json::from_str(&contents)?);
+        }
+        self.ledger_path = Some(path);
+        Ok(self)
+    }
+
+    pub fn is_dry_run(&self) -> bool {
+        self.dry_run
+    }
+
+    fn ledger(&self) -> MutexGuard<'_, Ledger> {
+        self.ledger.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    fn save(&self, ledger: &Ledger) -> Result<()> {
+        if let Some(path) = &self.ledger_path {
+            write_atomically(path, &serde_json::to_string_pretty(ledger)?)?;
+        }
+        Ok(())
+    }
+
+    fn check_locked(
+        &self,
+        ledger: &Ledger,
+        payload: &RequestWithdrawalPayload,
+        now: DateTime<Utc>,
+    ) -> std::result::Result<(), PolicyViolation> {
+        let allowed = self
+            .allowlist
+            .iter()
+            .find(|a| a.blockchain == payload.blockchain && a.address == payload.address)
+            .ok_or_else(|| PolicyViolation::AddressNotAllowed {
+                blockchain: payload.blockchain.clone(),
+                address: payload.address.clone(),
+            })?;
+
+        let usable_at = allowed.added_at + self.new_address_delay;
+        if now < usable_at {
+            return Err(PolicyViolation::AddressTooNew {
+                blockchain: payload.blockchain.clone(),
+                address: payload.address.clone(),
+                usable_at,
+            });
+        }
+
+        if let Some(&limit) = self.daily_limits.get(&payload.symbol) {
+            let used = ledger
+                .get(&now.date_naive())
+                .and_then(|day| day.get(&payload.symbol))
+                .copied()
+                .unwrap_or_default();
+            if used + payload.quantity > limit {
+                return Err(PolicyViolation::DailyLimitExceeded {
+                    symbol: payload.symbol.clone(),
+                    quantity: payload.quantity,
+                    used,
+                    limit,
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks `payload` against the policy without recording anything.
+    pub fn check(
+        &self,
+        payload: &RequestWithdrawalPayload,
+        now: DateTime<Utc>,
+    ) -> std::result::Result<(), PolicyViolation> {
+        self.check_locked(&self.ledger(), payload, now)
+    }
+
+    /// Checks `payload` and, outside dry-run mode, counts it against today's limit in the same
+    /// step so that concurrent withdrawals can't both slip under it.
+    pub fn authorize(&self, payload: &RequestWithdrawalPayload, now: DateTime<Utc>) -> Result<()> {
+        let mut ledger = self.ledger();
+        self.check_locked(&ledger, payload, now)?;
+        if self.dry_run {
+            return Ok(());
+        }
+
+        *ledger
+            .entry(now.date_naive())
+            .or_default()
+            .entry(payload.symbol.clone())
+            .or_default() += payload.quantity;
+        self.save(&ledger)
+    }
+
+    /// Takes back a quantity counted by `authorize` for a withdrawal that was definitely not
+    /// made. Withdrawals with an unknown outcome should stay counted.
+    pub fn revert(
+        &self,
+        payload: &RequestWithdrawalPayload,
+        authorized_at: DateTime<Utc>,
+    ) -> Result<()> {
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let mut ledger = self.ledger();
+        if let Some(used) = ledger
+            .get_mut(&authorized_at.date_naive())
+            .and_then(|day| day.get_mut(&payload.symbol))
+        {
+            *used = (*used - payload.quantity).max(Decimal::ZERO);
+        }
+        self.save(&ledger)
+    }
+}
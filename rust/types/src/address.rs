@@ -0,0 +1,292 @@
+use bech32::{FromBase32, Variant};
+use sha2::{Digest, Sha256};
+use sha3::Keccak256;
+
+use crate::Blockchain;
+
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum AddressError {
+    #[error("address is empty")]
+    Empty,
+
+    #[error("address is not valid base58")]
+    InvalidBase58,
+
+    #[error("address is not valid hex")]
+    InvalidHex,
+
+    #[error("address is not valid bech32: {0}")]
+    InvalidBech32(String),
+
+    #[error("address decodes to {actual} bytes, expected {expected}")]
+    InvalidLength { expected: usize, actual: usize },
+
+    #[error("address checksum does not match")]
+    InvalidChecksum,
+
+    #[error("address is not a recognised {0} address format")]
+    UnsupportedFormat(Blockchain),
+}
+
+impl Blockchain {
+    /// Checks that `address` is well formed for this chain, including its checksum where the
+    /// format has one. Chains this crate doesn't know are not checked.
+    pub fn validate_address(&self, address: &str) -> Result<(), AddressError> {
+        if address.is_empty() {
+            return Err(AddressError::Empty);
+        }
+        match self {
+            Blockchain::Solana => validate_solana(address),
+            Blockchain::Ethereum | Blockchain::Polygon => validate_evm(self, address),
+            Blockchain::Bitcoin => validate_bitcoin(address),
+            Blockchain::Unknown(_) => Ok(()),
+        }
+    }
+}
+
+/// A base58 encoded 32-byte ed25519 public key.
+fn validate_solana(address: &str) -> Result<(), AddressError> {
+    let bytes = bs58::decode(address)
+        .into_vec()
+        .map_err(|_| AddressError::InvalidBase58)?;
+    expect_len(&bytes, 32)
+}
+
+/// `0x` followed by 40 hex digits. Mixed-case addresses must carry a valid EIP-55 checksum;
+/// all-lowercase or all-uppercase ones carry none.
+fn validate_evm(blockchain: &Blockchain, address: &str) -> Result<(), AddressError> {
+    let hex = address
+        .strip_prefix("0x")
+        .ok_or_else(|| AddressError::UnsupportedFormat(blockchain.clone()))?;
+    if !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(AddressError::InvalidHex);
+    }
+    if hex.len() != 40 {
+        return Err(AddressError::InvalidLength {
+            expected: 20,
+            actual: hex.len() / 2,
+        });
+    }
+
+    let has_lower = hex.chars().any(|c| c.is_ascii_lowercase());
+    let has_upper = hex.chars().any(|c| c.is_ascii_uppercase());
+    if !(has_lower && has_upper) {
+        return Ok(());
+    }
+
+    let hash = Keccak256::digest(hex.to_ascii_lowercase().as_bytes());
+    for (i, c) in hex.chars().enumerate() {
+        let nibble = (hash[i / 2] >> (if i % 2 == 0 { 4 } else { 0 })) & 0x0f;
+        let expect_upper = nibble >= 8;
+        if c.is_ascii_alphabetic() && c.is_ascii_uppercase() != expect_upper {
+            return Err(AddressError::InvalidChecksum);
+        }
+    }
+    Ok(())
+}
+
+fn validate_bitcoin(address: &str) -> Result<(), AddressError> {
+    if address
+        .get(..3)
+        .is_some_and(|p| p.eq_ignore_ascii_case("bc1"))
+    {
+        validate_segwit(address)
+    } else {
+        validate_base58check(address)
+    }
+}
+
+/// Legacy P2PKH (version 0x00) and P2SH (version 0x05) addresses.
+fn validate_base58check(address: &str) -> Result<(), AddressError> {
+    let bytes = bs58::decode(address)
+        .into_vec()
+        .map_err(|_| AddressError::InvalidBase58)?;
+    expect_len(&bytes, 25)?;
+
+    let (payload, checksum) = bytes.split_at(21);
+    if Sha256::digest(Sha256::digest(payload))[..4] != *checksum {
+        return Err(AddressError::InvalidChecksum);
+    }
+    match payload[0] {
+        0x00 | 0x05 => Ok(()),
+        _ => Err(AddressError::UnsupportedFormat(Blockchain::Bitcoin)),
+    }
+}
+
+/// Native segwit addresses: bech32 for witness version 0 and bech32m for versions 1 to 16.
+fn validate_segwit(address: &str) -> Result<(), AddressError> {
+    let (hrp, data, variant) =
+        bech32::decode(address).map_err(|e| AddressError::InvalidBech32(e.to_string()))?;
+    if hrp != "bc" {
+        return Err(AddressError::UnsupportedFormat(Blockchain::Bitcoin));
+    }
+    let (version, program) = data
+        .split_first()
+        .ok_or_else(|| AddressError::InvalidBech32("missing witness version".to_string()))?;
+
+    // The checksum variant is tied to the witness version, so check it before the program.
+    let version = version.to_u8();
+    match (version, variant) {
+        (0, Variant::Bech32) | (1..=16, Variant::Bech32m) => {}
+        (0..=16, _) => return Err(AddressError::InvalidChecksum),
+        _ => return Err(AddressError::UnsupportedFormat(Blockchain::Bitcoin)),
+    }
+
+    let program =
+        Vec::<u8>::from_base32(program).map_err(|e| AddressError::InvalidBech32(e.to_string()))?;
+    match (version, program.len()) {
+        (0, 20 | 32) | (1..=16, 2..=40) => Ok(()),
+        (0, actual) => Err(AddressError::InvalidLength {
+            expected: 20,
+            actual,
+        }),
+        (_, actual) => Err(AddressError::InvalidLength {
+            expected: 32,
+            actual,
+        }),
+    }
+}
+
+fn expect_len(bytes: &[u8], expected: usize) -> Result<(), AddressError> {
+    if bytes.len() == expected {
+        Ok(())
+    } else {
+        Err(AddressError::InvalidLength {
+            expected,
+            actual: bytes.len(),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn solana() {
+        let chain = Blockchain::Solana;
+        assert_eq!(
+            chain.validate_address("11111111111111111111111111111111"),
+            Ok(())
+        );
+        assert_eq!(
+            chain.validate_address("So11111111111111111111111111111111111111112"),
+            Ok(())
+        );
+        assert_eq!(
+            chain.validate_address("0OIl"),
+            Err(AddressError::InvalidBase58)
+        );
+        assert!(matches!(
+            chain.validate_address("1111"),
+            Err(AddressError::InvalidLength { expected: 32, .. })
+        ));
+    }
+
+    #[test]
+    fn eip55_vectors() {
+        for address in [
+            "0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed",
+            "0xfB6916095ca1df60bB79Ce92cE3Ea74c37c5d359",
+            "0xdbF03B407c01E7cD3CBea99509d93f8DDDC8C6FB",
+            "0xD1220A0cf47c7B9Be7A2E6BA89F429762e7b9aDb",
+        ] {
+            assert_eq!(Blockchain::Ethereum.validate_address(address), Ok(()));
+            assert_eq!(Blockchain::Polygon.validate_address(address), Ok(()));
+        }
+    }
+
+    #[test]
+    fn evm_single_case_skips_checksum() {
+        let chain = Blockchain::Ethereum;
+        assert_eq!(
+            chain.validate_address("0x5aaeb6053f3e94c9b9a09f33669435e7ef1beaed"),
+            Ok(())
+        );
+        assert_eq!(
+            chain.validate_address("0x5AAEB6053F3E94C9B9A09F33669435E7EF1BEAED"),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn evm_rejects_bad_case_and_shape() {
+        let chain = Blockchain::Ethereum;
+        assert_eq!(
+            chain.validate_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeD"),
+            Err(AddressError::InvalidChecksum)
+        );
+        assert_eq!(
+            chain.validate_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAeg"),
+            Err(AddressError::InvalidHex)
+        );
+        assert!(matches!(
+            chain.validate_address("0x5aAeb6053F3E94C9b9A09f33669435E7Ef1BeA"),
+            Err(AddressError::InvalidLength { expected: 20, .. })
+        ));
+        assert!(matches!(
+            chain.validate_address("5aAeb6053F3E94C9b9A09f33669435E7Ef1BeAed"),
+            Err(AddressError::UnsupportedFormat(_))
+        ));
+    }
+
+    #[test]
+    fn bitcoin_base58check() {
+        let chain = Blockchain::Bitcoin;
+        assert_eq!(
+            chain.validate_address("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa"),
+            Ok(())
+        );
+        assert_eq!(
+            chain.validate_address("3J98t1WpEZ73CNmQviecrnyiWrnqRhWNLy"),
+            Ok(())
+        );
+        assert_eq!(
+            chain.validate_address("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNb"),
+            Err(AddressError::InvalidChecksum)
+        );
+    }
+
+    #[test]
+    fn bitcoin_segwit_vectors() {
+        let chain = Blockchain::Bitcoin;
+        for address in [
+            "BC1QW508D6QEJXTDG4Y5R3ZARVARY0C5XW7KV8F3T4",
+            "bc1qrp33g0q5c5txsp9arysrx4k6zdkfs4nce4xj0gdcccefvpysxf3qccfmv3",
+            "bc1p0xlxvlhemja6c4dqv22uapctqupfhlxm9h8z3k2e72q4k9hcz7vqzk5jj0",
+        ] {
+            assert_eq!(chain.validate_address(address), Ok(()), "{address}");
+        }
+    }
+
+    #[test]
+    fn bitcoin_segwit_rejects_wrong_variant_and_checksum() {
+        let chain = Blockchain::Bitcoin;
+        // Witness v0 encoded with bech32m.
+        assert_eq!(
+            chain.validate_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kemeawh"),
+            Err(AddressError::InvalidChecksum)
+        );
+        // Witness v2 encoded with bech32.
+        assert_eq!(
+            chain.validate_address("bc1zw508d6qejxtdg4y5r3zarvaryvqyzf3du"),
+            Err(AddressError::InvalidChecksum)
+        );
+        assert!(matches!(
+            chain.validate_address("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t5"),
+            Err(AddressError::InvalidBech32(_))
+        ));
+    }
+
+    #[test]
+    fn empty_and_unknown_chains() {
+        assert_eq!(
+            Blockchain::Solana.validate_address(""),
+            Err(AddressError::Empty)
+        );
+        assert_eq!(
+            Blockchain::Unknown("Sui".to_string()).validate_address("anything"),
+            Ok(())
+        );
+    }
+}
@@ -28,7 +28,7 @@ pub struct Deposit {
     pub created_at: chrono::NaiveDateTime,
 }
 
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize, EnumString, PartialEq, Eq, Hash)]
+#[derive(Debug, Display, Clone, Serialize, Deserialize, EnumString, PartialEq, Eq, Hash)]
 #[strum(serialize_all = "camelCase")]
 #[serde(rename_all = "camelCase")]
 pub enum DepositSource {
@@ -37,14 +37,22 @@ pub enum DepositSource {
     Ethereum,
     Bitcoin,
     Nuvei,
+    #[strum(default)]
+    #[cfg_attr(not(feature = "strict"), serde(untagged))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    Unknown(String),
 }
 
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize, EnumString, PartialEq, Eq, Hash)]
+#[derive(Debug, Display, Clone, Serialize, Deserialize, EnumString, PartialEq, Eq, Hash)]
 #[strum(serialize_all = "camelCase")]
 #[serde(rename_all = "camelCase")]
 pub enum DepositStatus {
     Pending,
     Confirmed,
+    #[strum(default)]
+    #[cfg_attr(not(feature = "strict"), serde(untagged))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    Unknown(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -87,7 +95,7 @@ pub struct Withdrawal {
     pub created_at: chrono::NaiveDateTime,
 }
 
-#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize, EnumString, PartialEq, Eq, Hash)]
+#[derive(Debug, Display, Clone, Serialize, Deserialize, EnumString, PartialEq, Eq, Hash)]
 #[strum(serialize_all = "camelCase")]
 #[serde(rename_all = "camelCase")]
 pub enum WithdrawalStatus {
@@ -95,4 +103,8 @@ pub enum WithdrawalStatus {
     Confirmed,
     Verifying,
     Void,
+    #[strum(default)]
+    #[cfg_attr(not(feature = "strict"), serde(untagged))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    Unknown(String),
 }
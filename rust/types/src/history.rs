@@ -0,0 +1,57 @@
+use chrono::{DateTime, Utc};
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use crate::order::Side;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Fill {
+    pub trade_id: Option<i64>,
+    pub order_id: String,
+    pub symbol: String,
+    pub side: Side,
+    pub price: Decimal,
+    pub quantity: Decimal,
+    pub fee: Decimal,
+    pub fee_symbol: String,
+    pub is_maker: bool,
+    pub timestamp: chrono::NaiveDateTime,
+}
+
+/// Filters shared by the order history and fill history endpoints.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct HistoryQuery {
+    pub symbol: Option<String>,
+    pub order_id: Option<String>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,
+    pub offset: Option<i64>,
+}
+
+impl HistoryQuery {
+    /// The query string parameters, with times in milliseconds since the Unix epoch.
+    pub fn params(&self) -> Vec<(&'static str, String)> {
+        let mut params = Vec::new();
+        if let Some(symbol) = &self.symbol {
+            params.push(("symbol", symbol.clone()));
+        }
+        if let Some(order_id) = &self.order_id {
+            params.push(("orderId", order_id.clone()));
+        }
+        if let Some(from) = self.from {
+            params.push(("from", from.timestamp_millis().to_string()));
+        }
+        if let Some(to) = self.to {
+            params.push(("to", to.timestamp_millis().to_string()));
+        }
+        if let Some(limit) = self.limit {
+            params.push(("limit", limit.to_string()));
+        }
+        if let Some(offset) = self.offset {
+            params.push(("offset", offset.to_string()));
+        }
+        params
+    }
+}
@@ -1,24 +1,18 @@
 use serde::{Deserialize, Serialize};
-use strum::{Display, EnumIter, EnumString};
+use strum::{Display, EnumString, IntoEnumIterator};
 
+pub mod address;
 pub mod capital;
+pub mod history;
 pub mod markets;
 pub mod order;
 pub mod trade;
 
+// Every exchange-provided enum ends in an `Unknown(String)` variant so that values added by the
+// exchange deserialize (and serialize back) unchanged instead of failing the whole response.
+// The `strict` feature turns those values back into deserialization errors.
 #[derive(
-    Debug,
-    Display,
-    Clone,
-    Copy,
-    Serialize,
-    Deserialize,
-    Default,
-    EnumString,
-    PartialEq,
-    Eq,
-    Hash,
-    EnumIter,
+    Debug, Display, Clone, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
 )]
 #[strum(serialize_all = "PascalCase")]
 #[serde(rename_all = "PascalCase")]
@@ -28,4 +22,23 @@ pub enum Blockchain {
     Ethereum,
     Polygon,
     Bitcoin,
+    #[strum(default)]
+    #[cfg_attr(not(feature = "strict"), serde(untagged))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    Unknown(String),
+}
+
+impl IntoEnumIterator for Blockchain {
+    type Iterator = std::array::IntoIter<Blockchain, 4>;
+
+    /// Iterates the known blockchains; `Unknown` is never yielded.
+    fn iter() -> Self::Iterator {
+        [
+            Blockchain::Solana,
+            Blockchain::Ethereum,
+            Blockchain::Polygon,
+            Blockchain::Bitcoin,
+        ]
+        .into_iter()
+    }
 }
@@ -1,5 +1,7 @@
-use rust_decimal::Decimal;
+use chrono::{DateTime, Datelike, Duration, TimeZone, Utc};
+use rust_decimal::{Decimal, RoundingStrategy};
 use serde::{Deserialize, Serialize};
+use strum::{Display, EnumString};
 
 use crate::Blockchain;
 
@@ -17,7 +19,7 @@ pub struct Ticker {
     pub trades: i64,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Market {
     pub symbol: String,
@@ -26,36 +28,132 @@ pub struct Market {
     pub filters: MarketFilters,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct MarketFilters {
-    price: PriceFilters,
-    quantity: QuantityFilters,
-    leverage: Option<LeverageFilters>,
+    pub price: PriceFilters,
+    pub quantity: QuantityFilters,
+    pub leverage: Option<LeverageFilters>,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct PriceFilters {
-    min_price: Decimal,
-    max_price: Option<Decimal>,
-    tick_size: Decimal,
+    pub min_price: Decimal,
+    pub max_price: Option<Decimal>,
+    pub tick_size: Decimal,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct QuantityFilters {
-    min_quantity: Decimal,
-    max_quantity: Option<Decimal>,
-    step_size: Decimal,
+    pub min_quantity: Decimal,
+    pub max_quantity: Option<Decimal>,
+    pub step_size: Decimal,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LeverageFilters {
-    min_leverage: Decimal,
-    max_leverage: Decimal,
-    step_size: Decimal,
+    pub min_leverage: Decimal,
+    pub max_leverage: Decimal,
+    pub step_size: Decimal,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RoundingMode {
+    Down,
+    Up,
+    Nearest,
+}
+
+impl From<RoundingMode> for RoundingStrategy {
+    fn from(mode: RoundingMode) -> Self {
+        match mode {
+            RoundingMode::Down => RoundingStrategy::ToNegativeInfinity,
+            RoundingMode::Up => RoundingStrategy::ToPositiveInfinity,
+            RoundingMode::Nearest => RoundingStrategy::MidpointAwayFromZero,
+        }
+    }
+}
+
+/// Rounds `value` to a multiple of `increment`. A zero increment leaves the value untouched.
+fn round_to_increment(value: Decimal, increment: Decimal, mode: RoundingMode) -> Decimal {
+    if increment.is_zero() {
+        return value;
+    }
+    (value / increment).round_dp_with_strategy(0, mode.into()) * increment
+}
+
+fn is_multiple_of(value: Decimal, increment: Decimal) -> bool {
+    increment.is_zero() || (value % increment).is_zero()
+}
+
+impl PriceFilters {
+    pub fn round(&self, price: Decimal, mode: RoundingMode) -> Decimal {
+        round_to_increment(price, self.tick_size, mode)
+    }
+
+    pub fn is_on_tick(&self, price: Decimal) -> bool {
+        is_multiple_of(price, self.tick_size)
+    }
+
+    pub fn in_bounds(&self, price: Decimal) -> bool {
+        price >= self.min_price && self.max_price.is_none_or(|max| price <= max)
+    }
+}
+
+impl QuantityFilters {
+    pub fn round(&self, quantity: Decimal, mode: RoundingMode) -> Decimal {
+        round_to_increment(quantity, self.step_size, mode)
+    }
+
+    pub fn is_on_step(&self, quantity: Decimal) -> bool {
+        is_multiple_of(quantity, self.step_size)
+    }
+
+    pub fn in_bounds(&self, quantity: Decimal) -> bool {
+        quantity >= self.min_quantity && self.max_quantity.is_none_or(|max| quantity <= max)
+    }
+}
+
+impl LeverageFilters {
+    /// Clamps `leverage` into the allowed range, then rounds it down onto the leverage step.
+    pub fn clamp(&self, leverage: Decimal) -> Decimal {
+        let clamped = leverage.clamp(self.min_leverage, self.max_leverage);
+        let stepped = round_to_increment(clamped, self.step_size, RoundingMode::Down);
+        stepped.max(self.min_leverage)
+    }
+}
+
+impl Market {
+    pub fn round_price(&self, price: Decimal, mode: RoundingMode) -> Decimal {
+        self.filters.price.round(price, mode)
+    }
+
+    pub fn round_quantity(&self, quantity: Decimal, mode: RoundingMode) -> Decimal {
+        self.filters.quantity.round(quantity, mode)
+    }
+
+    /// Checks that `price * quantity` reaches `min_notional`. The market filters don't publish a
+    /// notional floor, so the caller supplies the one that applies to its account.
+    pub fn min_notional_ok(
+        &self,
+        price: Decimal,
+        quantity: Decimal,
+        min_notional: Decimal,
+    ) -> bool {
+        price * quantity >= min_notional
+    }
+
+    /// Clamps `leverage` to the market's leverage filters. Markets without leverage filters
+    /// return the value unchanged.
+    pub fn clamp_leverage(&self, leverage: Decimal) -> Decimal {
+        match &self.filters.leverage {
+            Some(filters) => filters.clamp(leverage),
+            None => leverage,
+        }
+    }
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -78,15 +176,318 @@ pub struct OrderBookDepth {
     pub last_update_id: String,
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Display, Clone, Copy, Serialize, Deserialize, EnumString, PartialEq, Eq, Hash)]
+pub enum KlineInterval {
+    #[strum(serialize = "1m")]
+    #[serde(rename = "1m")]
+    OneMinute,
+    #[strum(serialize = "3m")]
+    #[serde(rename = "3m")]
+    ThreeMinutes,
+    #[strum(serialize = "5m")]
+    #[serde(rename = "5m")]
+    FiveMinutes,
+    #[strum(serialize = "15m")]
+    #[serde(rename = "15m")]
+    FifteenMinutes,
+    #[strum(serialize = "30m")]
+    #[serde(rename = "30m")]
+    ThirtyMinutes,
+    #[strum(serialize = "1h")]
+    #[serde(rename = "1h")]
+    OneHour,
+    #[strum(serialize = "2h")]
+    #[serde(rename = "2h")]
+    TwoHours,
+    #[strum(serialize = "4h")]
+    #[serde(rename = "4h")]
+    FourHours,
+    #[strum(serialize = "6h")]
+    #[serde(rename = "6h")]
+    SixHours,
+    #[strum(serialize = "8h")]
+    #[serde(rename = "8h")]
+    EightHours,
+    #[strum(serialize = "12h")]
+    #[serde(rename = "12h")]
+    TwelveHours,
+    #[strum(serialize = "1d")]
+    #[serde(rename = "1d")]
+    OneDay,
+    #[strum(serialize = "3d")]
+    #[serde(rename = "3d")]
+    ThreeDays,
+    #[strum(serialize = "1w")]
+    #[serde(rename = "1w")]
+    OneWeek,
+    #[strum(serialize = "1month")]
+    #[serde(rename = "1month")]
+    OneMonth,
+}
+
+impl KlineInterval {
+    /// The length of one candle. Months vary in length, so `OneMonth` reports a nominal 30 days;
+    /// use `next` to step across calendar months.
+    pub fn duration(&self) -> Duration {
+        match self {
+            KlineInterval::OneMinute => Duration::minutes(1),
+            KlineInterval::ThreeMinutes => Duration::minutes(3),
+            KlineInterval::FiveMinutes => Duration::minutes(5),
+            KlineInterval::FifteenMinutes => Duration::minutes(15),
+            KlineInterval::ThirtyMinutes => Duration::minutes(30),
+            KlineInterval::OneHour => Duration::hours(1),
+            KlineInterval::TwoHours => Duration::hours(2),
+            KlineInterval::FourHours => Duration::hours(4),
+            KlineInterval::SixHours => Duration::hours(6),
+            KlineInterval::EightHours => Duration::hours(8),
+            KlineInterval::TwelveHours => Duration::hours(12),
+            KlineInterval::OneDay => Duration::days(1),
+            KlineInterval::ThreeDays => Duration::days(3),
+            KlineInterval::OneWeek => Duration::weeks(1),
+            KlineInterval::OneMonth => Duration::days(30),
+        }
+    }
+
+    /// Rounds `time` down to the start of the candle containing it. Weeks start on Monday and
+    /// months on the first day, both at 00:00 UTC.
+    pub fn align(&self, time: DateTime<Utc>) -> DateTime<Utc> {
+        if *self == KlineInterval::OneMonth {
+            return Utc
+                .with_ymd_and_hms(time.year(), time.month(), 1, 0, 0, 0)
+                .single()
+                .expect("first of the month is a valid UTC time");
+        }
+
+        // The Unix epoch is a Thursday; weekly candles are anchored on the Monday before it.
+        let origin = match self {
+            KlineInterval::OneWeek => -Duration::days(3).num_seconds(),
+            _ => 0,
+        };
+        let step = self.duration().num_seconds();
+        let aligned = (time.timestamp() - origin).div_euclid(step) * step + origin;
+        Utc.timestamp_opt(aligned, 0)
+            .single()
+            .expect("aligned timestamp precedes a valid timestamp")
+    }
+
+    /// The start of the candle following the one that starts at `start`.
+    pub fn next(&self, start: DateTime<Utc>) -> DateTime<Utc> {
+        if *self != KlineInterval::OneMonth {
+            return start + self.duration();
+        }
+        let (year, month) = match start.month() {
+            12 => (start.year() + 1, 1),
+            month => (start.year(), month + 1),
+        };
+        Utc.with_ymd_and_hms(year, month, 1, 0, 0, 0)
+            .single()
+            .expect("first of the month is a valid UTC time")
+    }
+}
+
+impl From<KlineInterval> for Duration {
+    fn from(interval: KlineInterval) -> Self {
+        interval.duration()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct Kline {
-    pub start: String,
+    #[serde(with = "kline_time")]
+    pub start: DateTime<Utc>,
     pub open: Option<Decimal>,
     pub high: Option<Decimal>,
     pub low: Option<Decimal>,
     pub close: Option<Decimal>,
-    pub end: Option<String>,
+    #[serde(default, with = "kline_time::option")]
+    pub end: Option<DateTime<Utc>>,
     pub volume: Decimal,
     pub trades: u64,
 }
+
+impl Kline {
+    /// A candle for an interval in which nothing traded.
+    pub fn empty(start: DateTime<Utc>, end: DateTime<Utc>) -> Self {
+        Self {
+            start,
+            open: None,
+            high: None,
+            low: None,
+            close: None,
+            end: Some(end),
+            volume: Decimal::ZERO,
+            trades: 0,
+        }
+    }
+
+    /// Checks that high and low bound the open and close. Candles without trades carry no
+    /// prices at all and are consistent.
+    pub fn is_consistent(&self) -> bool {
+        match (self.open, self.high, self.low, self.close) {
+            (None, None, None, None) => true,
+            (Some(open), Some(high), Some(low), Some(close)) => {
+                high >= open.max(close) && open.min(close) >= low
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Kline timestamps are UTC times without an offset, e.g. `2024-01-01T00:00:00`.
+mod kline_time {
+    use chrono::{DateTime, NaiveDateTime, Utc};
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    const FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+    fn parse(s: &str) -> Result<DateTime<Utc>, chrono::ParseError> {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%dT%H:%M:%S%.f")
+            .or_else(|_| NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S%.f"))
+            .map(|t| t.and_utc())
+    }
+
+    pub fn serialize<S: Serializer>(
+        time: &DateTime<Utc>,
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(&time.format(FORMAT))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<DateTime<Utc>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        parse(&s).map_err(de::Error::custom)
+    }
+
+    pub mod option {
+        use super::*;
+
+        pub fn serialize<S: Serializer>(
+            time: &Option<DateTime<Utc>>,
+            serializer: S,
+        ) -> Result<S::Ok, S::Error> {
+            match time {
+                Some(time) => super::serialize(time, serializer),
+                None => serializer.serialize_none(),
+            }
+        }
+
+        pub fn deserialize<'de, D: Deserializer<'de>>(
+            deserializer: D,
+        ) -> Result<Option<DateTime<Utc>>, D::Error> {
+            Option::<String>::deserialize(deserializer)?
+                .map(|s| parse(&s).map_err(de::Error::custom))
+                .transpose()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utc(y: i32, m: u32, d: u32, h: u32, min: u32, sec: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, sec).unwrap()
+    }
+
+    #[test]
+    fn align_rounds_down_to_the_candle_start() {
+        let time = utc(2024, 3, 14, 15, 9, 26);
+        assert_eq!(
+            KlineInterval::OneMinute.align(time),
+            utc(2024, 3, 14, 15, 9, 0)
+        );
+        assert_eq!(
+            KlineInterval::FifteenMinutes.align(time),
+            utc(2024, 3, 14, 15, 0, 0)
+        );
+        assert_eq!(
+            KlineInterval::FourHours.align(time),
+            utc(2024, 3, 14, 12, 0, 0)
+        );
+        assert_eq!(KlineInterval::OneDay.align(time), utc(2024, 3, 14, 0, 0, 0));
+        assert_eq!(
+            KlineInterval::OneMonth.align(time),
+            utc(2024, 3, 1, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn align_keeps_candle_starts() {
+        let start = utc(2024, 3, 14, 12, 0, 0);
+        assert_eq!(KlineInterval::FourHours.align(start), start);
+        assert_eq!(KlineInterval::OneHour.align(start), start);
+    }
+
+    #[test]
+    fn weeks_start_on_monday() {
+        // 2024-03-14 is a Thursday and 2024-03-11 the Monday before it.
+        let monday = utc(2024, 3, 11, 0, 0, 0);
+        assert_eq!(
+            KlineInterval::OneWeek.align(utc(2024, 3, 14, 15, 9, 26)),
+            monday
+        );
+        assert_eq!(KlineInterval::OneWeek.align(monday), monday);
+        assert_eq!(
+            KlineInterval::OneWeek.align(utc(2024, 3, 17, 23, 59, 59)),
+            monday
+        );
+        // Before the epoch, which is a Thursday.
+        assert_eq!(
+            KlineInterval::OneWeek.align(utc(1970, 1, 1, 0, 0, 0)),
+            utc(1969, 12, 29, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn next_steps_one_candle() {
+        let start = utc(2024, 3, 14, 12, 0, 0);
+        assert_eq!(
+            KlineInterval::FourHours.next(start),
+            utc(2024, 3, 14, 16, 0, 0)
+        );
+        assert_eq!(
+            KlineInterval::OneWeek.next(utc(2024, 3, 11, 0, 0, 0)),
+            utc(2024, 3, 18, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn next_month_follows_the_calendar() {
+        let month = KlineInterval::OneMonth;
+        assert_eq!(
+            month.next(utc(2024, 1, 1, 0, 0, 0)),
+            utc(2024, 2, 1, 0, 0, 0)
+        );
+        assert_eq!(
+            month.next(utc(2024, 2, 1, 0, 0, 0)),
+            utc(2024, 3, 1, 0, 0, 0)
+        );
+        assert_eq!(
+            month.next(utc(2024, 12, 1, 0, 0, 0)),
+            utc(2025, 1, 1, 0, 0, 0)
+        );
+    }
+
+    #[test]
+    fn kline_consistency() {
+        let start = utc(2024, 3, 14, 0, 0, 0);
+        let mut kline = Kline::empty(start, KlineInterval::OneDay.next(start));
+        assert!(kline.is_consistent());
+
+        kline.open = Some(Decimal::from(10));
+        assert!(!kline.is_consistent());
+
+        kline.high = Some(Decimal::from(12));
+        kline.low = Some(Decimal::from(9));
+        kline.close = Some(Decimal::from(11));
+        assert!(kline.is_consistent());
+
+        kline.close = Some(Decimal::from(13));
+        assert!(!kline.is_consistent());
+        kline.close = Some(Decimal::from(8));
+        assert!(!kline.is_consistent());
+    }
+}
@@ -1,5 +1,5 @@
 use rust_decimal::Decimal;
-use serde::{Deserialize, Serialize};
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
 use strum::{Display, EnumString};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -40,7 +40,7 @@ pub struct LimitOrder {
 }
 
 #[derive(
-    Debug, Display, Clone, Copy, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
+    Debug, Display, Clone, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
 )]
 #[strum(serialize_all = "PascalCase")]
 #[serde(rename_all = "PascalCase")]
@@ -48,17 +48,263 @@ pub enum OrderType {
     #[default]
     Limit,
     Market,
+    #[strum(default)]
+    #[cfg_attr(not(feature = "strict"), serde(untagged))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    Unknown(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(tag = "orderType")]
+/// An order of a type this crate doesn't model yet (e.g. a new stop order type). The fields
+/// shared by every order type are parsed; everything else is kept in `raw`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GenericOrder {
+    pub order_type: OrderType,
+    pub id: String,
+    pub client_id: Option<u32>,
+    pub symbol: String,
+    pub side: Side,
+    pub quantity: Option<Decimal>,
+    #[serde(default)]
+    pub executed_quantity: Decimal,
+    #[serde(default)]
+    pub executed_quote_quantity: Decimal,
+    pub price: Option<Decimal>,
+    pub trigger_price: Option<Decimal>,
+    pub status: OrderStatus,
+    pub created_at: i64,
+    #[serde(skip)]
+    pub raw: serde_json::Value,
+}
+
+#[derive(Debug, Clone)]
 pub enum Order {
     Market(MarketOrder),
     Limit(LimitOrder),
+    Generic(GenericOrder),
+}
+
+impl Serialize for Order {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        #[derive(Serialize)]
+        #[serde(tag = "orderType")]
+        enum Tagged<'a> {
+            Market(&'a MarketOrder),
+            Limit(&'a LimitOrder),
+        }
+
+        match self {
+            Order::Market(order) => Tagged::Market(order).serialize(serializer),
+            Order::Limit(order) => Tagged::Limit(order).serialize(serializer),
+            Order::Generic(order) => order.raw.serialize(serializer),
+        }
+    }
+}
+
+impl<'de> Deserialize<'de> for Order {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let raw = serde_json::Value::deserialize(deserializer)?;
+        let order_type = raw
+            .get("orderType")
+            .and_then(serde_json::Value::as_str)
+            .ok_or_else(|| de::Error::missing_field("orderType"))?;
+
+        match order_type {
+            "Market" => MarketOrder::deserialize(raw)
+                .map(Order::Market)
+                .map_err(de::Error::custom),
+            "Limit" => LimitOrder::deserialize(raw)
+                .map(Order::Limit)
+                .map_err(de::Error::custom),
+            other if cfg!(feature = "strict") => {
+                Err(de::Error::unknown_variant(other, &["Market", "Limit"]))
+            }
+            _ => {
+                let mut order = GenericOrder::deserialize(&raw).map_err(de::Error::custom)?;
+                order.raw = raw;
+                Ok(Order::Generic(order))
+            }
+        }
+    }
+}
+
+impl Order {
+    pub fn id(&self) -> &str {
+        match self {
+            Order::Market(order) => &order.id,
+            Order::Limit(order) => &order.id,
+            Order::Generic(order) => &order.id,
+        }
+    }
+
+    pub fn client_id(&self) -> Option<u32> {
+        match self {
+            Order::Market(order) => order.client_id,
+            Order::Limit(order) => order.client_id,
+            Order::Generic(order) => order.client_id,
+        }
+    }
+
+    pub fn symbol(&self) -> &str {
+        match self {
+            Order::Market(order) => &order.symbol,
+            Order::Limit(order) => &order.symbol,
+            Order::Generic(order) => &order.symbol,
+        }
+    }
+
+    pub fn side(&self) -> Side {
+        match self {
+            Order::Market(order) => order.side.clone(),
+            Order::Limit(order) => order.side.clone(),
+            Order::Generic(order) => order.side.clone(),
+        }
+    }
+
+    pub fn order_type(&self) -> OrderType {
+        match self {
+            Order::Market(_) => OrderType::Market,
+            Order::Limit(_) => OrderType::Limit,
+            Order::Generic(order) => order.order_type.clone(),
+        }
+    }
+
+    pub fn status(&self) -> OrderStatus {
+        match self {
+            Order::Market(order) => order.status.clone(),
+            Order::Limit(order) => order.status.clone(),
+            Order::Generic(order) => order.status.clone(),
+        }
+    }
+
+    pub fn created_at(&self) -> i64 {
+        match self {
+            Order::Market(order) => order.created_at,
+            Order::Limit(order) => order.created_at,
+            Order::Generic(order) => order.created_at,
+        }
+    }
+
+    /// The limit price; market orders have none.
+    pub fn price(&self) -> Option<Decimal> {
+        match self {
+            Order::Market(_) => None,
+            Order::Limit(order) => Some(order.price),
+            Order::Generic(order) => order.price,
+        }
+    }
+
+    pub fn trigger_price(&self) -> Option<Decimal> {
+        match self {
+            Order::Market(order) => order.trigger_price,
+            Order::Limit(order) => order.trigger_price,
+            Order::Generic(order) => order.trigger_price,
+        }
+    }
+
+    /// The base quantity ordered; market orders sized in the quote asset have none.
+    pub fn quantity(&self) -> Option<Decimal> {
+        match self {
+            Order::Market(order) => order.quantity,
+            Order::Limit(order) => Some(order.quantity),
+            Order::Generic(order) => order.quantity,
+        }
+    }
+
+    pub fn executed_quantity(&self) -> Decimal {
+        match self {
+            Order::Market(order) => order.executed_quantity,
+            Order::Limit(order) => order.executed_quantity,
+            Order::Generic(order) => order.executed_quantity,
+        }
+    }
+
+    pub fn executed_quote_quantity(&self) -> Decimal {
+        match self {
+            Order::Market(order) => order.executed_quote_quantity,
+            Order::Limit(order) => order.executed_quote_quantity,
+            Order::Generic(order) => order.executed_quote_quantity,
+        }
+    }
+
+    /// The base quantity still to be filled, when the order was sized in the base asset.
+    pub fn remaining_quantity(&self) -> Option<Decimal> {
+        self.quantity()
+            .map(|quantity| (quantity - self.executed_quantity()).max(Decimal::ZERO))
+    }
+
+    /// The volume-weighted fill price, or `None` while nothing has been executed.
+    pub fn average_fill_price(&self) -> Option<Decimal> {
+        let executed = self.executed_quantity();
+        if executed.is_zero() {
+            None
+        } else {
+            Some(self.executed_quote_quantity() / executed)
+        }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.status().is_open()
+    }
+
+    pub fn is_terminal(&self) -> bool {
+        self.status().is_terminal()
+    }
+
+    pub fn snapshot(&self) -> OrderSnapshot {
+        OrderSnapshot::from(self)
+    }
+}
+
+/// A flattened view of an `Order` for consumers that don't want to match on the order type.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OrderSnapshot {
+    pub id: String,
+    pub client_id: Option<u32>,
+    pub symbol: String,
+    pub order_type: OrderType,
+    pub side: Side,
+    pub status: OrderStatus,
+    pub price: Option<Decimal>,
+    pub trigger_price: Option<Decimal>,
+    pub quantity: Option<Decimal>,
+    pub executed_quantity: Decimal,
+    pub executed_quote_quantity: Decimal,
+    pub remaining_quantity: Option<Decimal>,
+    pub average_fill_price: Option<Decimal>,
+    pub created_at: i64,
+}
+
+impl From<&Order> for OrderSnapshot {
+    fn from(order: &Order) -> Self {
+        Self {
+            id: order.id().to_string(),
+            client_id: order.client_id(),
+            symbol: order.symbol().to_string(),
+            order_type: order.order_type(),
+            side: order.side(),
+            status: order.status(),
+            price: order.price(),
+            trigger_price: order.trigger_price(),
+            quantity: order.quantity(),
+            executed_quantity: order.executed_quantity(),
+            executed_quote_quantity: order.executed_quote_quantity(),
+            remaining_quantity: order.remaining_quantity(),
+            average_fill_price: order.average_fill_price(),
+            created_at: order.created_at(),
+        }
+    }
+}
+
+impl From<Order> for OrderSnapshot {
+    fn from(order: Order) -> Self {
+        Self::from(&order)
+    }
 }
 
 #[derive(
-    Debug, Display, Clone, Copy, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
+    Debug, Display, Clone, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
 )]
 #[strum(serialize_all = "UPPERCASE")]
 #[serde(rename_all = "UPPERCASE")]
@@ -67,10 +313,14 @@ pub enum TimeInForce {
     GTC,
     IOC,
     FOK,
+    #[strum(default)]
+    #[cfg_attr(not(feature = "strict"), serde(untagged))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    Unknown(String),
 }
 
 #[derive(
-    Debug, Display, Clone, Copy, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
+    Debug, Display, Clone, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
 )]
 #[strum(serialize_all = "PascalCase")]
 #[serde(rename_all = "PascalCase")]
@@ -80,10 +330,14 @@ pub enum SelfTradePrevention {
     RejectMaker,
     RejectBoth,
     Allow,
+    #[strum(default)]
+    #[cfg_attr(not(feature = "strict"), serde(untagged))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    Unknown(String),
 }
 
 #[derive(
-    Debug, Display, Clone, Copy, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
+    Debug, Display, Clone, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
 )]
 #[strum(serialize_all = "PascalCase")]
 #[serde(rename_all = "PascalCase")]
@@ -95,10 +349,33 @@ pub enum OrderStatus {
     New,
     PartiallyFilled,
     Triggered,
+    #[strum(default)]
+    #[cfg_attr(not(feature = "strict"), serde(untagged))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    Unknown(String),
+}
+
+impl OrderStatus {
+    /// Whether an order in this status can still trade.
+    pub fn is_open(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::New | OrderStatus::PartiallyFilled | OrderStatus::Triggered
+        )
+    }
+
+    /// Whether this status is final and will not change again. Unknown statuses are neither
+    /// open nor terminal.
+    pub fn is_terminal(&self) -> bool {
+        matches!(
+            self,
+            OrderStatus::Cancelled | OrderStatus::Expired | OrderStatus::Filled
+        )
+    }
 }
 
 #[derive(
-    Debug, Display, Clone, Copy, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
+    Debug, Display, Clone, Serialize, Deserialize, Default, EnumString, PartialEq, Eq, Hash,
 )]
 #[strum(serialize_all = "PascalCase")]
 #[serde(rename_all = "PascalCase")]
@@ -106,6 +383,10 @@ pub enum Side {
     #[default]
     Bid,
     Ask,
+    #[strum(default)]
+    #[cfg_attr(not(feature = "strict"), serde(untagged))]
+    #[cfg_attr(feature = "strict", serde(skip))]
+    Unknown(String),
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
@@ -147,3 +428,150 @@ pub struct CancelOrderPayload {
 pub struct CancelOpenOrdersPayload {
     pub symbol: String,
 }
+
+/// Marker types tracking which `OrderRequest` options are still valid to set.
+pub mod state {
+    /// A limit order that can still be made post-only or given a time in force.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Limit;
+
+    /// A limit order that must rest on the book. It is always good-til-cancelled, so it takes
+    /// no other time in force.
+    #[derive(Debug, Clone, Copy)]
+    pub struct PostOnly;
+
+    /// A limit order with an explicit time in force; it can no longer be made post-only.
+    #[derive(Debug, Clone, Copy)]
+    pub struct TimedLimit;
+
+    /// A market order sized either in the base or the quote asset.
+    #[derive(Debug, Clone, Copy)]
+    pub struct Market;
+
+    /// A limit order that is only placed once the trigger price is reached.
+    #[derive(Debug, Clone, Copy)]
+    pub struct StopLimit;
+}
+
+/// Builds an `ExecuteOrderPayload` whose options are checked at compile time, e.g. a post-only
+/// order can't be given an IOC time in force and a market order can't carry both quantities.
+#[derive(Debug, Clone)]
+pub struct OrderRequest<S> {
+    payload: ExecuteOrderPayload,
+    state: std::marker::PhantomData<S>,
+}
+
+impl<S> OrderRequest<S> {
+    fn new(payload: ExecuteOrderPayload) -> Self {
+        Self {
+            payload,
+            state: std::marker::PhantomData,
+        }
+    }
+
+    fn into_state<T>(self) -> OrderRequest<T> {
+        OrderRequest::new(self.payload)
+    }
+
+    pub fn stp(mut self, self_trade_prevention: SelfTradePrevention) -> Self {
+        self.payload.self_trade_prevention = Some(self_trade_prevention);
+        self
+    }
+
+    pub fn client_id(mut self, client_id: u32) -> Self {
+        self.payload.client_id = Some(client_id);
+        self
+    }
+
+    pub fn payload(&self) -> &ExecuteOrderPayload {
+        &self.payload
+    }
+
+    pub fn build(self) -> ExecuteOrderPayload {
+        self.payload
+    }
+}
+
+impl OrderRequest<state::Limit> {
+    pub fn limit(symbol: impl Into<String>, side: Side, price: Decimal, quantity: Decimal) -> Self {
+        Self::new(ExecuteOrderPayload {
+            order_type: OrderType::Limit,
+            symbol: symbol.into(),
+            side,
+            price: Some(price),
+            quantity: Some(quantity),
+            ..Default::default()
+        })
+    }
+
+    pub fn post_only(mut self) -> OrderRequest<state::PostOnly> {
+        self.payload.post_only = Some(true);
+        self.payload.time_in_force = Some(TimeInForce::GTC);
+        self.into_state()
+    }
+
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> OrderRequest<state::TimedLimit> {
+        self.payload.time_in_force = Some(time_in_force);
+        self.into_state()
+    }
+}
+
+impl OrderRequest<state::Market> {
+    /// A market order for `quantity` units of the base asset.
+    pub fn market_base(symbol: impl Into<String>, side: Side, quantity: Decimal) -> Self {
+        Self::new(ExecuteOrderPayload {
+            order_type: OrderType::Market,
+            symbol: symbol.into(),
+            side,
+            quantity: Some(quantity),
+            ..Default::default()
+        })
+    }
+
+    /// A market order spending or receiving `quote_quantity` units of the quote asset.
+    pub fn market_quote(symbol: impl Into<String>, side: Side, quote_quantity: Decimal) -> Self {
+        Self::new(ExecuteOrderPayload {
+            order_type: OrderType::Market,
+            symbol: symbol.into(),
+            side,
+            quote_quantity: Some(quote_quantity),
+            ..Default::default()
+        })
+    }
+
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.payload.time_in_force = Some(time_in_force);
+        self
+    }
+}
+
+impl OrderRequest<state::StopLimit> {
+    pub fn stop_limit(
+        symbol: impl Into<String>,
+        side: Side,
+        trigger_price: Decimal,
+        price: Decimal,
+        quantity: Decimal,
+    ) -> Self {
+        Self::new(ExecuteOrderPayload {
+            order_type: OrderType::Limit,
+            symbol: symbol.into(),
+            side,
+            price: Some(price),
+            quantity: Some(quantity),
+            trigger_price: Some(trigger_price),
+            ..Default::default()
+        })
+    }
+
+    pub fn time_in_force(mut self, time_in_force: TimeInForce) -> Self {
+        self.payload.time_in_force = Some(time_in_force);
+        self
+    }
+}
+
+impl<S> From<OrderRequest<S>> for ExecuteOrderPayload {
+    fn from(request: OrderRequest<S>) -> Self {
+        request.payload
+    }
+}